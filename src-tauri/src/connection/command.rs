@@ -0,0 +1,223 @@
+use redis::{Arg, Cmd};
+
+// commands which never modify the dataset
+// used to route commands to the replica
+const READONLY_COMMANDS: &[&str] = &[
+    "get",
+    "mget",
+    "strlen",
+    "getrange",
+    "substr",
+    "lcs",
+    "getbit",
+    "bitcount",
+    "bitpos",
+    "bitfield_ro",
+    "exists",
+    "type",
+    "ttl",
+    "pttl",
+    "expiretime",
+    "pexpiretime",
+    "scan",
+    "keys",
+    "randomkey",
+    "dbsize",
+    "dump",
+    "object",
+    "sort_ro",
+    "hget",
+    "hgetall",
+    "hkeys",
+    "hvals",
+    "hlen",
+    "hmget",
+    "hexists",
+    "hstrlen",
+    "hscan",
+    "hrandfield",
+    "lrange",
+    "llen",
+    "lindex",
+    "lpos",
+    "smembers",
+    "scard",
+    "sismember",
+    "smismember",
+    "sscan",
+    "srandmember",
+    "sinter",
+    "sintercard",
+    "sunion",
+    "sdiff",
+    "zrange",
+    "zrangebyscore",
+    "zrangebylex",
+    "zrevrange",
+    "zrevrangebyscore",
+    "zrevrangebylex",
+    "zcard",
+    "zcount",
+    "zlexcount",
+    "zscore",
+    "zmscore",
+    "zrank",
+    "zrevrank",
+    "zscan",
+    "zrandmember",
+    "zdiff",
+    "zinter",
+    "zunion",
+    "zintercard",
+    "pfcount",
+    "geopos",
+    "geodist",
+    "geohash",
+    "geosearch",
+    "georadius_ro",
+    "georadiusbymember_ro",
+    "xrange",
+    "xrevrange",
+    "xlen",
+    "xinfo",
+    "xpending",
+    "json.get",
+    "json.mget",
+    "json.type",
+    "json.strlen",
+    "json.arrlen",
+    "json.arrindex",
+    "json.objkeys",
+    "json.objlen",
+    "json.debug",
+    "json.resp",
+    "ts.get",
+    "ts.mget",
+    "ts.range",
+    "ts.revrange",
+    "ts.mrange",
+    "ts.mrevrange",
+    "ts.info",
+    "ts.queryindex",
+    "bf.exists",
+    "bf.mexists",
+    "bf.info",
+    "bf.card",
+    "cf.exists",
+    "cf.mexists",
+    "cf.count",
+    "cf.info",
+    "cms.query",
+    "cms.info",
+    "topk.query",
+    "topk.count",
+    "topk.list",
+    "topk.info",
+    "tdigest.info",
+    "tdigest.rank",
+    "tdigest.revrank",
+    "tdigest.byrank",
+    "tdigest.byrevrank",
+    "tdigest.quantile",
+    "tdigest.cdf",
+    "tdigest.max",
+    "tdigest.min",
+    "tdigest.trimmed_mean",
+];
+
+// the server and connection commands which never modify the server
+// they are not in the flags of COMMAND INFO as readonly, but never need confirmation
+const SAFE_COMMANDS: &[&str] = &[
+    "info",
+    "ping",
+    "echo",
+    "time",
+    "lastsave",
+    "role",
+    "command",
+    "readonly",
+    "readwrite",
+    "select",
+    "auth",
+    "hello",
+    "monitor",
+    "subscribe",
+    "psubscribe",
+    "ssubscribe",
+    "unsubscribe",
+    "punsubscribe",
+    "sunsubscribe",
+    "pubsub",
+];
+
+// the sub commands of the admin commands which only read
+const SAFE_SUBCOMMANDS: &[(&str, &str)] = &[
+    ("client", "setname"),
+    ("client", "getname"),
+    ("client", "list"),
+    ("client", "info"),
+    ("client", "id"),
+    ("config", "get"),
+    ("cluster", "nodes"),
+    ("cluster", "info"),
+    ("cluster", "slots"),
+    ("cluster", "shards"),
+    ("cluster", "myid"),
+    ("cluster", "keyslot"),
+    ("cluster", "countkeysinslot"),
+    ("cluster", "getkeysinslot"),
+    ("cluster", "links"),
+    ("slowlog", "get"),
+    ("slowlog", "len"),
+    ("latency", "latest"),
+    ("latency", "history"),
+    ("latency", "doctor"),
+    ("memory", "stats"),
+    ("memory", "doctor"),
+    ("memory", "malloc-stats"),
+    ("acl", "whoami"),
+    ("acl", "list"),
+    ("acl", "users"),
+    ("acl", "getuser"),
+    ("acl", "cat"),
+    ("acl", "log"),
+    ("module", "list"),
+    ("function", "list"),
+    ("function", "stats"),
+    ("script", "exists"),
+];
+
+// get the lowercase command name and the lowercase sub command
+pub fn get_name(cmd: &Cmd) -> (String, Option<String>) {
+    let mut args = cmd.args_iter().filter_map(|arg| match arg {
+        Arg::Simple(v) => Some(String::from_utf8_lossy(v).to_lowercase()),
+        Arg::Cursor => None,
+    });
+    let name = args.next().unwrap_or_default();
+    (name, args.next())
+}
+
+// the command is readonly or not
+// unknown commands are treated as write commands
+pub fn is_readonly(cmd: &Cmd) -> bool {
+    let (name, sub) = get_name(cmd);
+    match name.as_str() {
+        "memory" => sub.as_deref() == Some("usage"),
+        s => READONLY_COMMANDS.contains(&s),
+    }
+}
+
+// the command never modifies the data or the server, such as INFO and CONFIG GET
+pub fn is_safe(cmd: &Cmd) -> bool {
+    if is_readonly(cmd) {
+        return true;
+    }
+    let (name, sub) = get_name(cmd);
+    if SAFE_COMMANDS.contains(&name.as_str()) {
+        return true;
+    }
+    match sub {
+        Some(sub) => SAFE_SUBCOMMANDS.contains(&(name.as_str(), sub.as_str())),
+        None => false,
+    }
+}
@@ -1,44 +1,73 @@
 use crate::{
-    connection::{CValue, Node},
+    connection::{command, CValue, ConnectionState, Node},
     err::CusError,
     model::Command,
+    proxy::{self, ProxyParams},
+    redact,
     ssh::{self, SshProxy},
     utils,
 };
 use chrono::prelude::*;
-use redis::cluster::{ClusterClient, ClusterConnection as RedisSyncClusterConnection};
+use redis::cluster::{
+    ClusterClient, ClusterClientBuilder, ClusterConnection as RedisSyncClusterConnection,
+};
 use redis::cluster_async::ClusterConnection;
 use redis::Arg;
 use redis::Client;
 use redis::Connection as RedisSyncConnection;
 use redis::FromRedisValue;
+use redis::PushInfo;
 use redis::{
     aio::{ConnectionLike, MultiplexedConnection},
     AsyncConnectionConfig,
 };
 
-use redis::aio::Monitor;
+use redis::aio::{Monitor, PubSub};
 use ssh_jumper::model::SshForwarderEnd;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex as SMutex;
+use std::time::Duration;
 use tokio::sync::oneshot::Receiver;
+use tokio::sync::{broadcast, mpsc, Mutex};
+
+// the client name set to the server
+pub const CLIENT_NAME: &str = "tauri-redis";
+// the push messages kept for slow receivers
+const PUSH_BUFFER: usize = 1024;
 
 #[derive(Clone, Debug)]
 pub struct ConnectedParam {
     pub tcp_host: String,
     pub tcp_port: u16,
+    // connect by the unix domain socket instead of tcp
+    pub socket_path: Option<String>,
+    pub protocol: redis::ProtocolVersion,
+    pub tls: bool,
     pub username: Option<String>,
     pub password: Option<String>,
 }
 
 impl redis::IntoConnectionInfo for ConnectedParam {
     fn into_connection_info(self) -> redis::RedisResult<redis::ConnectionInfo> {
+        let addr = match &self.socket_path {
+            Some(path) => redis::ConnectionAddr::Unix(path.into()),
+            None if self.tls => redis::ConnectionAddr::TcpTls {
+                host: self.tcp_host.clone(),
+                port: self.tcp_port,
+                insecure: false,
+                tls_params: None,
+            },
+            None => redis::ConnectionAddr::Tcp(self.tcp_host.clone(), self.tcp_port),
+        };
         Ok(redis::ConnectionInfo {
-            addr: redis::ConnectionAddr::Tcp(self.tcp_host.clone(), self.tcp_port),
+            addr,
             redis: redis::RedisConnectionInfo {
                 db: 0,
                 username: self.username,
                 password: self.password,
-                protocol: redis::ProtocolVersion::RESP2,
+                protocol: self.protocol,
             },
         })
     }
@@ -48,15 +77,62 @@ impl redis::IntoConnectionInfo for ConnectedParam {
 pub struct ConnectionParams {
     pub redis_params: ConnectedParam,
     pub ssh_params: Option<ssh::SshParams>,
+    // socks5 or http proxy, not used with ssh
+    pub proxy_params: Option<ProxyParams>,
     pub is_cluster: bool,
+    // send read commands to replica
+    pub read_from_replica: bool,
+    // the replica address of a standalone server
+    pub replica_params: Option<ConnectedParam>,
+    // the master name, redis_params is the address of the sentinel
+    pub sentinel_master: Option<String>,
+    pub sentinel_username: Option<String>,
+    pub sentinel_password: Option<String>,
+    pub connect_timeout: Option<Duration>,
+    pub response_timeout: Option<Duration>,
+    // the write commands must be confirmed by the user
+    pub confirm_write: bool,
+}
+
+impl ConnectionParams {
+    // the params to connect the replica of a standalone server
+    pub fn get_replica_params(&self) -> Option<ConnectionParams> {
+        if self.is_cluster || !self.read_from_replica {
+            return None;
+        }
+        let replica = self.replica_params.clone()?;
+        let mut p = self.clone();
+        if let Some(mut ssh) = p.ssh_params {
+            ssh.target_host = replica.tcp_host.clone();
+            ssh.target_port = replica.tcp_port;
+            p.ssh_params = Some(ssh)
+        }
+        if let Some(mut proxy) = p.proxy_params {
+            proxy.target_host = replica.tcp_host.clone();
+            proxy.target_port = replica.tcp_port;
+            p.proxy_params = Some(proxy)
+        }
+        p.redis_params = replica;
+        p.replica_params = None;
+        p.read_from_replica = false;
+        Some(p)
+    }
 }
 
 pub trait Connectable {
     fn get_params(&self) -> ConnectionParams;
 }
 
+impl Connectable for ConnectionParams {
+    fn get_params(&self) -> ConnectionParams {
+        self.clone()
+    }
+}
+
 pub struct Connection {
     pub params: ConnectionParams,
+    // the params before resolved by sentinel, used to reconnect
+    pub origin: ConnectionParams,
     pub cancel_tunnel_rx: Option<Receiver<SshForwarderEnd>>,
     pub tunnel_addr: Option<SocketAddr>,
 }
@@ -69,6 +145,9 @@ impl SshProxy for Connection {
     fn get_ssh_config(&self) -> Option<ssh::SshParams> {
         self.params.ssh_params.clone()
     }
+    fn get_proxy_config(&self) -> Option<ProxyParams> {
+        self.params.proxy_params.clone()
+    }
     fn close_tunnel(&mut self) {
         if let Some(mut rx) = self.cancel_tunnel_rx.take() {
             rx.close();
@@ -88,6 +167,7 @@ impl Drop for Connection {
 impl Connection {
     pub fn new(params: ConnectionParams) -> Self {
         Self {
+            origin: params.clone(),
             params,
             cancel_tunnel_rx: None,
             tunnel_addr: None,
@@ -106,12 +186,100 @@ impl Connection {
     }
     // get the connection host
     pub fn get_host(&self) -> String {
+        if let Some(path) = &self.params.redis_params.socket_path {
+            return format!("unix://{}", path);
+        }
         format!(
             "redis://{}:{}",
             self.params.redis_params.tcp_host.clone(),
             self.params.redis_params.tcp_port
         )
     }
+    // the config of async connection
+    pub fn get_async_config(&self) -> AsyncConnectionConfig {
+        let mut config = AsyncConnectionConfig::new();
+        if let Some(d) = self.params.connect_timeout {
+            config = config.set_connection_timeout(d);
+        }
+        if let Some(d) = self.params.response_timeout {
+            config = config.set_response_timeout(d);
+        }
+        config
+    }
+
+    // turn the redis error of connecting to the timeout error
+    fn map_connect_err(&self, e: redis::RedisError) -> CusError {
+        match self.params.connect_timeout {
+            Some(d) if e.is_timeout() => CusError::connect_timeout(d),
+            _ => CusError::Redis(e),
+        }
+    }
+
+    // replace the sentinel address with the address of the master
+    // the sentinel is reached through the tunnel, which is closed after resolved
+    async fn resolve_sentinel(&mut self) -> Result<(), CusError> {
+        let master = match self.params.sentinel_master.take() {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+        self.create_tunnel().await?;
+        let addr = self.get_master_addr(&master).await;
+        self.close_tunnel();
+        self.tunnel_addr = None;
+        let (host, port) = addr?;
+        self.params.redis_params.tcp_host = host.clone();
+        self.params.redis_params.tcp_port = port;
+        if let Some(ssh) = self.params.ssh_params.as_mut() {
+            ssh.target_host = host.clone();
+            ssh.target_port = port;
+        }
+        if let Some(proxy) = self.params.proxy_params.as_mut() {
+            proxy.target_host = host;
+            proxy.target_port = port;
+        }
+        Ok(())
+    }
+
+    async fn get_master_addr(&self, master: &str) -> Result<(String, u16), CusError> {
+        let mut sentinel = self.get_connected_params();
+        sentinel.username = self.params.sentinel_username.clone();
+        sentinel.password = self.params.sentinel_password.clone();
+        let client = Client::open(sentinel)?;
+        let mut conn = client
+            .get_multiplexed_async_connection_with_config(&self.get_async_config())
+            .await
+            .map_err(|e| self.map_connect_err(e))?;
+        let addr: Option<(String, u16)> = redis::cmd("SENTINEL")
+            .arg("GET-MASTER-ADDR-BY-NAME")
+            .arg(master)
+            .query_async(&mut conn)
+            .await?;
+        addr.ok_or_else(|| CusError::App(format!("Sentinel master not found: {}", master)))
+    }
+
+    // open the ssh tunnel or the proxy forwarder if set
+    pub async fn open_tunnel(&mut self) -> Result<(), CusError> {
+        if self.params.redis_params.socket_path.is_some() {
+            if self.params.ssh_params.is_some() || self.params.proxy_params.is_some() {
+                return Err(CusError::build(
+                    "Unix socket can not be used with SSH or proxy",
+                ));
+            }
+            return Ok(());
+        }
+        self.resolve_sentinel().await?;
+        self.create_tunnel().await
+    }
+
+    async fn create_tunnel(&mut self) -> Result<(), CusError> {
+        if self.params.ssh_params.is_some() {
+            ssh::create_tunnel(self).await?;
+        } else {
+            proxy::create_tunnel(self).await?;
+        }
+        Ok(())
+    }
+
     // get the redis params
     // if proxy set
     // host/port will be replaced
@@ -134,13 +302,32 @@ impl Connection {
         }
     }
 
+    // the dedicated async pubsub connection, the tunnel must be opened before
+    pub async fn get_pubsub(&self) -> Result<PubSub, CusError> {
+        let params = self.get_connected_params();
+        let client = Client::open(params)?;
+        let result = match self.params.connect_timeout {
+            Some(d) => tokio::time::timeout(d, client.get_async_pubsub())
+                .await
+                .map_err(|_| CusError::connect_timeout(d))?,
+            None => client.get_async_pubsub().await,
+        };
+        result.map_err(|e| self.map_connect_err(e))
+    }
+
     pub async fn get_sync_one(&self) -> Result<RedisSyncConnection, CusError> {
         let params = self.get_connected_params();
         let client = Client::open(params)?;
-        let result = client.get_connection();
+        let result = match self.params.connect_timeout {
+            Some(d) => client.get_connection_with_timeout(d),
+            None => client.get_connection(),
+        };
         match result {
-            Ok(c) => Ok(c),
-            Err(e) => Err(CusError::App(e.to_string())),
+            Ok(c) => {
+                c.set_read_timeout(self.params.response_timeout)?;
+                Ok(c)
+            }
+            Err(e) => Err(self.map_connect_err(e)),
         }
     }
 
@@ -158,54 +345,172 @@ impl Connection {
         &mut self,
         config: &AsyncConnectionConfig,
     ) -> Result<MultiplexedConnection, CusError> {
-        ssh::create_tunnel(self).await?;
+        self.open_tunnel().await?;
         let params = self.get_connected_params();
         let client: Client = Client::open(params)?;
         let conn: MultiplexedConnection = client
             .get_multiplexed_async_connection_with_config(config)
+            .await
+            .map_err(|e| self.map_connect_err(e))?;
+        Ok(conn)
+    }
+    // connect to the db through the opened tunnel
+    pub async fn get_normal_db(
+        &self,
+        config: &AsyncConnectionConfig,
+        db: u8,
+    ) -> redis::RedisResult<MultiplexedConnection> {
+        let params = self.get_connected_params();
+        let client: Client = Client::open(params)?;
+        let mut conn: MultiplexedConnection = client
+            .get_multiplexed_async_connection_with_config(config)
+            .await?;
+        redis::cmd("SELECT")
+            .arg(db)
+            .query_async::<()>(&mut conn)
             .await?;
+        let _ = redis::cmd("CLIENT")
+            .arg("SETNAME")
+            .arg(CLIENT_NAME)
+            .query_async::<()>(&mut conn)
+            .await;
         Ok(conn)
     }
+
     pub async fn get_cluster(&mut self) -> Result<ClusterConnection, CusError> {
-        ssh::create_tunnel(self).await?;
+        self.open_tunnel().await?;
         let params = self.get_connected_params();
-        let client = ClusterClient::new(vec![params])?;
+        let mut builder = ClusterClientBuilder::new(vec![params]);
+        if self.params.read_from_replica {
+            builder = builder.read_from_replicas();
+        }
+        builder = builder.use_protocol(self.params.redis_params.protocol);
+        if let Some(d) = self.params.connect_timeout {
+            builder = builder.connection_timeout(d);
+        }
+        if let Some(d) = self.params.response_timeout {
+            builder = builder.response_timeout(d);
+        }
+        let client = builder.build()?;
         let r = client.get_async_connection().await;
         match r {
             Ok(connection) => Ok(connection),
-            Err(_) => Err(CusError::App(String::from("Connection Timeout"))),
+            Err(e) => Err(self.map_connect_err(e)),
+        }
+    }
+}
+
+fn resp3_required() -> CusError {
+    CusError::build("Push messages require RESP3 on a standalone server")
+}
+
+#[derive(Clone)]
+pub enum ConnectionKind {
+    Normal(MultiplexedConnection),
+    Cluster(ClusterConnection),
+}
+
+impl ConnectionLike for ConnectionKind {
+    fn req_packed_command<'a>(
+        &'a mut self,
+        cmd: &'a redis::Cmd,
+    ) -> redis::RedisFuture<'a, redis::Value> {
+        match self {
+            ConnectionKind::Normal(c) => c.req_packed_command(cmd),
+            ConnectionKind::Cluster(c) => c.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a redis::Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+        match self {
+            ConnectionKind::Normal(c) => c.req_packed_commands(cmd, offset, count),
+            ConnectionKind::Cluster(c) => c.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            ConnectionKind::Normal(c) => c.get_db(),
+            ConnectionKind::Cluster(c) => c.get_db(),
         }
     }
 }
 
 pub struct ConnectionWrapper {
-    pub conn: Box<dyn ConnectionLike + Send>,
+    conn: ConnectionKind,
+    // multiplexed connections of the other dbs, db 0 use conn
+    // so requests to different dbs never switch db on a shared connection
+    dbs: Mutex<HashMap<u8, MultiplexedConnection>>,
     pub id: String,
-    pub nodes: Vec<Node>,
-    pub db: u8,
+    // the id of the connection model, set when added to the manager
+    pub cid: Option<u32>,
+    pub nodes: Mutex<Vec<Node>>,
     pub created_at: DateTime<Local>,
     pub model: Connection,
-    pub version: Option<String>,
+    pub version: SMutex<Option<String>>,
+    // the replica of standalone server, read commands are sent to it
+    pub replica: Option<Box<ConnectionWrapper>>,
+    state: SMutex<ConnectionState>,
+    // the connection is dropped, should be rebuilt
+    broken: AtomicBool,
+    // the push messages of RESP3 received by the shared connection
+    push: Option<broadcast::Sender<PushInfo>>,
+    // the subscriptions of every channel by the command, such as ("subscribe", "news")
+    // the channel is unsubscribed when the last subscription leaves it
+    subscribed: Mutex<HashMap<(String, String), usize>>,
 }
 
 impl ConnectionWrapper {
     pub async fn build<T: Connectable>(model: T) -> Result<Self, CusError> {
-        let b: Box<dyn ConnectionLike + Send>;
         let params: ConnectionParams = model.get_params();
+        let replica_params = params.get_replica_params();
+        let mut wrapper = Self::connect(params).await?;
+        if let Some(p) = replica_params {
+            wrapper.replica = Some(Box::new(Self::connect(p).await?));
+        }
+        Ok(wrapper)
+    }
+
+    async fn connect(params: ConnectionParams) -> Result<Self, CusError> {
         let mut connection = Connection::new(params);
-        if connection.params.is_cluster {
-            b = Box::new(connection.get_cluster().await?)
+        let mut push = None;
+        let conn = if connection.params.is_cluster {
+            ConnectionKind::Cluster(connection.get_cluster().await?)
         } else {
-            b = Box::new(connection.get_normal(&AsyncConnectionConfig::new()).await?);
-        }
+            let mut config = connection.get_async_config();
+            if connection.params.redis_params.protocol == redis::ProtocolVersion::RESP3 {
+                let (tx, mut rx) = mpsc::unbounded_channel::<PushInfo>();
+                let (push_tx, _) = broadcast::channel::<PushInfo>(PUSH_BUFFER);
+                let sender = push_tx.clone();
+                tokio::spawn(async move {
+                    while let Some(info) = rx.recv().await {
+                        let _ = sender.send(info);
+                    }
+                });
+                config = config.set_push_sender(tx);
+                push = Some(push_tx);
+            }
+            ConnectionKind::Normal(connection.get_normal(&config).await?)
+        };
         let r = Self {
             id: utils::random_str(32),
-            nodes: vec![],
-            db: 0,
+            cid: None,
+            nodes: Mutex::new(vec![]),
+            dbs: Mutex::new(HashMap::new()),
             created_at: Local::now(),
             model: connection,
-            conn: b,
-            version: None,
+            conn,
+            version: SMutex::new(None),
+            replica: None,
+            state: SMutex::new(ConnectionState::Connected),
+            broken: AtomicBool::new(false),
+            push,
+            subscribed: Mutex::new(HashMap::new()),
         };
         Ok(r)
     }
@@ -218,10 +523,101 @@ impl ConnectionWrapper {
         self.model.is_cluster()
     }
 
+    pub fn get_state(&self) -> ConnectionState {
+        *self.state.lock().unwrap()
+    }
+
+    pub fn set_state(&self, state: ConnectionState) {
+        *self.state.lock().unwrap() = state;
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.broken.load(Ordering::Relaxed)
+    }
+
+    pub fn set_broken(&self) {
+        self.broken.store(true, Ordering::Relaxed);
+    }
+
+    // receive the push messages of the shared connection
+    // only standalone server with RESP3 supports it
+    pub fn subscribe_push(&self) -> Result<broadcast::Receiver<PushInfo>, CusError> {
+        match &self.push {
+            Some(tx) => Ok(tx.subscribe()),
+            None => Err(resp3_required()),
+        }
+    }
+
+    // send the (un)subscribe command on the shared connection
+    // the reply is a push message, so no response is waited
+    // the subscriptions share the connection, a channel is only subscribed once
+    // and unsubscribed after every subscription of it is closed
+    pub async fn send_pubsub(&self, cmd: &str, channels: &[String]) -> Result<(), CusError> {
+        let (kind, add) = match cmd {
+            "subscribe" | "psubscribe" | "ssubscribe" => (cmd, true),
+            "unsubscribe" | "punsubscribe" | "sunsubscribe" => (&cmd[2..], false),
+            _ => return Err(CusError::App(format!("invalid command: {}", cmd))),
+        };
+        if let ConnectionKind::Normal(c) = &self.conn {
+            if self.push.is_some() {
+                let mut conn = c.clone();
+                // held while sending, so the counts follow the order of the commands
+                let mut subscribed = self.subscribed.lock().await;
+                for channel in channels {
+                    let key = (kind.to_string(), channel.clone());
+                    let count = subscribed.get(&key).copied().unwrap_or_default();
+                    let count = if add {
+                        count + 1
+                    } else {
+                        count.saturating_sub(1)
+                    };
+                    // the first subscription or the last unsubscription is sent
+                    if (add && count == 1) || (!add && count == 0) {
+                        match cmd {
+                            "subscribe" => conn.subscribe(channel).await?,
+                            "unsubscribe" => conn.unsubscribe(channel).await?,
+                            "psubscribe" => conn.psubscribe(channel).await?,
+                            "punsubscribe" => conn.punsubscribe(channel).await?,
+                            // sharded channels, the server must own the slot of the channel
+                            _ => redis::cmd(cmd).arg(channel).exec_async(&mut conn).await?,
+                        }
+                    }
+                    if count == 0 {
+                        subscribed.remove(&key);
+                    } else {
+                        subscribed.insert(key, count);
+                    }
+                }
+                return Ok(());
+            }
+        }
+        Err(resp3_required())
+    }
+
+    // get the connection of the db
+    // cluster only has db 0
+    pub async fn get_conn(&self, db: Option<u8>) -> redis::RedisResult<ConnectionKind> {
+        let db = match (&self.conn, db) {
+            (ConnectionKind::Normal(_), Some(db)) if db != 0 => db,
+            _ => return Ok(self.conn.clone()),
+        };
+        let mut dbs = self.dbs.lock().await;
+        if let Some(c) = dbs.get(&db) {
+            return Ok(ConnectionKind::Normal(c.clone()));
+        }
+        let c = self
+            .model
+            .get_normal_db(&self.model.get_async_config(), db)
+            .await?;
+        dbs.insert(db, c.clone());
+        Ok(ConnectionKind::Normal(c))
+    }
+
     // execute the redis command
     pub async fn execute<T>(
-        &mut self,
-        cmd: &mut redis::Cmd,
+        &self,
+        cmd: &redis::Cmd,
+        db: Option<u8>,
     ) -> Result<(T, Command), (CusError, Command)>
     where
         T: FromRedisValue,
@@ -240,53 +636,56 @@ impl ConnectionWrapper {
                 Arg::Cursor => {}
             }
         }
+        redact::args(&mut cmd_vec);
+        let target = match &self.replica {
+            Some(replica) if command::is_readonly(cmd) => replica.as_ref(),
+            _ => self,
+        };
         let start = Local::now();
-        let value_r = cmd.query_async(self).await;
+        let value_r = match target.get_conn(db).await {
+            Ok(mut conn) => cmd.query_async(&mut conn).await,
+            Err(e) => Err(e),
+        };
         let end = Local::now();
-        let mut rep: Vec<String> = vec![];
         let mut cus_cmd = Command {
             id: utils::random_str(32),
+            connection_id: self.cid,
             cmd: cmd_vec.join(" "),
             response: CValue::Nil,
+            success: true,
             created_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-            host: self.model.get_host(),
+            host: target.get_host(),
             duration: end.timestamp_micros() - start.timestamp_micros(),
         };
         match value_r {
             Ok(value) => match T::from_redis_value(&value) {
                 Ok(v) => {
                     cus_cmd.response = CValue::build(value);
+                    redact::response(&cmd_vec, &mut cus_cmd.response);
                     Ok((v, cus_cmd))
                 }
-                Err(err) => Err((CusError::App(err.to_string()), cus_cmd)),
+                Err(err) => {
+                    cus_cmd.success = false;
+                    Err((CusError::App(err.to_string()), cus_cmd))
+                }
             },
             Err(err) => {
-                rep.push(err.to_string());
                 cus_cmd.response = CValue::Str(err.to_string());
+                cus_cmd.success = false;
+                // a slow command times out, the connection is still usable
+                if err.is_timeout() {
+                    let e = match target.model.params.response_timeout {
+                        Some(d) => CusError::response_timeout(d),
+                        None => CusError::App(err.to_string()),
+                    };
+                    return Err((e, cus_cmd));
+                }
+                if err.is_connection_dropped() || err.is_connection_refusal() || err.is_io_error() {
+                    tracing::warn!(host = %cus_cmd.host, error = %err, "connection broken");
+                    self.set_broken();
+                }
                 Err((CusError::App(err.to_string()), cus_cmd))
             }
         }
     }
 }
-
-impl ConnectionLike for ConnectionWrapper {
-    fn req_packed_command<'a>(
-        &'a mut self,
-        cmd: &'a redis::Cmd,
-    ) -> redis::RedisFuture<'a, redis::Value> {
-        self.conn.req_packed_command(cmd)
-    }
-
-    fn req_packed_commands<'a>(
-        &'a mut self,
-        cmd: &'a redis::Pipeline,
-        offset: usize,
-        count: usize,
-    ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
-        self.conn.req_packed_commands(cmd, offset, count)
-    }
-
-    fn get_db(&self) -> i64 {
-        self.conn.get_db()
-    }
-}
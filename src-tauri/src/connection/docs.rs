@@ -0,0 +1,258 @@
+use redis::{FromRedisValue, Value};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{connection::ConnectionWrapper, err::CusError, utils};
+
+// the commands running a script or a function
+const SCRIPT_COMMANDS: [&str; 3] = ["eval", "evalsha", "fcall"];
+
+// an argument of COMMAND DOCS
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CommandArg {
+    pub name: String,
+    // key, string, integer, double, pattern, unix-time, pure-token, oneof or block
+    pub kind: String,
+    pub token: Option<String>,
+    pub optional: bool,
+    pub multiple: bool,
+    pub arguments: Vec<CommandArg>,
+}
+
+impl CommandArg {
+    fn build(v: &Value) -> Self {
+        let mut arg = Self::default();
+        for (field, value) in pairs(v) {
+            match field.as_str() {
+                "name" => arg.name = string(value),
+                "type" => arg.kind = string(value),
+                "token" => arg.token = Some(string(value)),
+                "flags" => {
+                    let flags: Vec<String> =
+                        FromRedisValue::from_redis_value(value).unwrap_or_default();
+                    arg.optional = flags.iter().any(|f| f == "optional");
+                    arg.multiple = flags.iter().any(|f| f == "multiple");
+                }
+                "arguments" => arg.arguments = list(value).iter().map(Self::build).collect(),
+                _ => {}
+            }
+        }
+        arg
+    }
+
+    // the redis doc style, such as [EX seconds | PX milliseconds]
+    pub fn signature(&self) -> String {
+        let inner = match self.kind.as_str() {
+            "pure-token" => self
+                .token
+                .clone()
+                .unwrap_or_else(|| self.name.to_uppercase()),
+            "oneof" => self
+                .arguments
+                .iter()
+                .map(|a| a.signature())
+                .collect::<Vec<_>>()
+                .join(" | "),
+            "block" => self
+                .arguments
+                .iter()
+                .map(|a| a.signature())
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => self.name.clone(),
+        };
+        let inner = match (&self.token, self.kind.as_str()) {
+            (_, "pure-token") | (None, _) => inner,
+            (Some(token), _) => format!("{} {}", token, inner),
+        };
+        let inner = if self.multiple {
+            format!("{} [{} ...]", inner, inner)
+        } else {
+            inner
+        };
+        if self.optional {
+            format!("[{}]", inner)
+        } else {
+            inner
+        }
+    }
+
+    // the tokens of the argument and its children, such as EX, NX
+    fn tokens(&self, result: &mut Vec<String>) {
+        if let Some(token) = &self.token {
+            result.push(token.clone());
+        }
+        self.arguments.iter().for_each(|a| a.tokens(result));
+    }
+
+    fn has_key(&self) -> bool {
+        self.kind == "key" || self.arguments.iter().any(|a| a.has_key())
+    }
+}
+
+// a command of COMMAND INFO merged with COMMAND DOCS
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CommandDoc {
+    // lowercase, the sub command is such as config|get
+    pub name: String,
+    pub summary: String,
+    pub group: String,
+    pub since: String,
+    pub complexity: String,
+    pub arity: i64,
+    // readonly, write, admin...
+    pub flags: Vec<String>,
+    pub arguments: Vec<CommandArg>,
+    pub subcommands: BTreeMap<String, CommandDoc>,
+}
+
+impl CommandDoc {
+    // the entry of COMMAND INFO: name, arity, flags, first key, last key, step, ..., subcommands
+    fn from_info(v: &Value) -> Option<Self> {
+        let items = list(v);
+        let mut doc = Self {
+            name: string(items.first()?).to_lowercase(),
+            arity: FromRedisValue::from_redis_value(items.get(1)?).unwrap_or_default(),
+            flags: FromRedisValue::from_redis_value(items.get(2)?).unwrap_or_default(),
+            ..Default::default()
+        };
+        if let Some(subcommands) = items.get(9) {
+            for sub in list(subcommands).iter().filter_map(Self::from_info) {
+                doc.subcommands.insert(sub.name.clone(), sub);
+            }
+        }
+        Some(doc)
+    }
+
+    fn merge_docs(&mut self, v: &Value) {
+        for (field, value) in pairs(v) {
+            match field.as_str() {
+                "summary" => self.summary = string(value),
+                "group" => self.group = string(value),
+                "since" => self.since = string(value),
+                "complexity" => self.complexity = string(value),
+                "arguments" => self.arguments = list(value).iter().map(CommandArg::build).collect(),
+                "subcommands" => {
+                    for (name, value) in pairs(value) {
+                        let name = name.to_lowercase();
+                        self.subcommands
+                            .entry(name.clone())
+                            .or_insert_with(|| CommandDoc {
+                                name,
+                                ..Default::default()
+                            })
+                            .merge_docs(value);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // the name typed, the sub command is separated by space
+    pub fn display_name(&self) -> String {
+        self.name.replace('|', " ").to_uppercase()
+    }
+
+    pub fn signature(&self) -> String {
+        let mut s = self.display_name();
+        for arg in &self.arguments {
+            s.push(' ');
+            s.push_str(&arg.signature());
+        }
+        s
+    }
+
+    pub fn tokens(&self) -> Vec<String> {
+        let mut result = vec![];
+        self.arguments.iter().for_each(|a| a.tokens(&mut result));
+        result.dedup();
+        result
+    }
+
+    pub fn has_key(&self) -> bool {
+        self.arguments.iter().any(|a| a.has_key())
+    }
+}
+
+// the commands of a server version
+#[derive(Debug, Default)]
+pub struct CommandDocs {
+    pub commands: BTreeMap<String, CommandDoc>,
+}
+
+impl CommandDocs {
+    pub async fn load(conn: &ConnectionWrapper, version: &str) -> Result<Self, CusError> {
+        let mut docs = Self::default();
+        // the wrapper is used directly, the manager loads the docs to classify the commands
+        let (info, _) = conn
+            .execute::<Value>(&redis::cmd("COMMAND"), None)
+            .await
+            .map_err(|(e, _)| e)?;
+        for doc in list(&info).iter().filter_map(CommandDoc::from_info) {
+            docs.commands.insert(doc.name.clone(), doc);
+        }
+        // COMMAND DOCS is available since 7.0
+        if utils::compare_version(version, "7.0.0") >= 0 {
+            let (value, _) = conn
+                .execute::<Value>(redis::cmd("COMMAND").arg("DOCS"), None)
+                .await
+                .map_err(|(e, _)| e)?;
+            for (name, v) in pairs(&value) {
+                if let Some(doc) = docs.commands.get_mut(&name.to_lowercase()) {
+                    doc.merge_docs(v);
+                }
+            }
+        }
+        Ok(docs)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandDoc> {
+        self.commands.get(&name.to_lowercase())
+    }
+
+    // the command writes the data or changes the server by the flags of COMMAND INFO
+    // the flags of the sub command are used since 7.0, none if the command is unknown
+    // the scripts have no write flag but can write anything, the _ro variants can not
+    pub fn is_write(&self, name: &str, sub: Option<&str>) -> Option<bool> {
+        if SCRIPT_COMMANDS.contains(&name.to_lowercase().as_str()) {
+            return Some(true);
+        }
+        let mut doc = self.get(name)?;
+        if let Some(sub) = sub {
+            let full = format!("{}|{}", doc.name, sub.to_lowercase());
+            if let Some(s) = doc.subcommands.get(&full) {
+                doc = s;
+            }
+        }
+        Some(
+            doc.flags
+                .iter()
+                .any(|f| f == "write" || f == "admin" || f == "may_replicate"),
+        )
+    }
+}
+
+fn string(v: &Value) -> String {
+    String::from_redis_value(v).unwrap_or_default()
+}
+
+fn list(v: &Value) -> Vec<Value> {
+    match v {
+        Value::Array(items) | Value::Set(items) => items.clone(),
+        _ => vec![],
+    }
+}
+
+// the map of RESP3 or the flat array of RESP2
+fn pairs(v: &Value) -> Vec<(String, &Value)> {
+    match v {
+        Value::Map(items) => items.iter().map(|(k, v)| (string(k), v)).collect(),
+        Value::Array(items) => items
+            .chunks(2)
+            .filter(|c| c.len() == 2)
+            .map(|c| (string(&c[0]), &c[1]))
+            .collect(),
+        _ => vec![],
+    }
+}
@@ -1,40 +1,70 @@
 use crate::{
-    connection::{Connectable, ConnectionWrapper, Node},
+    connection::{
+        command, CommandDocs, Connectable, ConnectionState, ConnectionWrapper, Node, CLIENT_NAME,
+    },
     err::CusError,
     model::Command,
     response::{self, Field},
+    sqlite::CommandLog,
 };
-use redis::cluster::ClusterConnection as RedisSyncClusterConnection;
-use redis::{Cmd, Connection as RedisSyncConnection};
+use redis::Cmd;
 use redis::{FromRedisValue, Value};
 use std::collections::HashMap;
-use tokio::sync::{mpsc::Sender, Mutex};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify, RwLock};
+
+// the commands kept for the slow receivers
+const DEBUG_BUFFER: usize = 1024;
+
+tokio::task_local! {
+    // the write commands of the current request are confirmed by the user
+    pub static WRITE_CONFIRMED: bool;
+}
 
 /**
  * connection manager state
+ * the lock is only held to get the connection, never across a request
+ * so requests of different connections/dbs run in parallel
  */
 pub struct Manager {
-    pub map: Mutex<HashMap<u32, ConnectionWrapper>>,
-    debug_tx: Mutex<Vec<Sender<Command>>>,
+    map: RwLock<HashMap<u32, Arc<ConnectionWrapper>>>,
+    // the executed commands, received by the log windows and the sqlite writer
+    debug_tx: broadcast::Sender<Command>,
+    // wake up the supervisor when a connection is broken
+    broken: Notify,
+    // the command docs cached by the server version
+    docs: RwLock<HashMap<String, Arc<CommandDocs>>>,
 }
 
 impl Manager {
     pub fn new() -> Manager {
+        let (debug_tx, _) = broadcast::channel::<Command>(DEBUG_BUFFER);
+        CommandLog::start(debug_tx.subscribe());
         Manager {
-            map: Mutex::new(HashMap::new()),
-            debug_tx: Mutex::new(vec![]),
+            map: RwLock::new(HashMap::new()),
+            debug_tx,
+            broken: Notify::new(),
+            docs: RwLock::new(HashMap::new()),
         }
     }
-    pub async fn add(&self, id: u32, conn: ConnectionWrapper) {
-        self.map.lock().await.insert(id, conn);
-        if let Some(conn) = self.map.lock().await.get_mut(&id) {
-            let _ = self.set_name(conn, "tauri-redis".to_string()).await;
+    pub async fn add(&self, id: u32, mut conn: ConnectionWrapper) {
+        conn.cid = Some(id);
+        let _ = self.set_name(&conn, CLIENT_NAME.to_string()).await;
+        self.map.write().await.insert(id, Arc::new(conn));
+    }
+
+    // get the opened connection
+    pub async fn get(&self, id: u32) -> Result<Arc<ConnectionWrapper>, CusError> {
+        match self.map.read().await.get(&id) {
+            Some(conn) => Ok(conn.clone()),
+            None => Err(CusError::connection_not_found()),
         }
     }
 
     pub async fn set_name(
         &self,
-        conn: &mut ConnectionWrapper,
+        conn: &ConnectionWrapper,
         name: String,
     ) -> Result<String, CusError> {
         self.execute_with::<String>(redis::cmd("CLIENT").arg("SETNAME").arg(&name), conn)
@@ -42,16 +72,14 @@ impl Manager {
     }
 
     pub async fn get_config(&self, id: u32, pattern: &str) -> Result<Vec<Field>, CusError> {
-        if let Some(conn) = self.map.lock().await.get_mut(&id) {
-            return self.get_config_with(pattern, conn).await;
-        }
-        Err(CusError::connection_not_found())
+        let conn = self.get(id).await?;
+        self.get_config_with(pattern, &conn).await
     }
 
     pub async fn get_config_with(
         &self,
         pattern: &str,
-        conn: &mut ConnectionWrapper,
+        conn: &ConnectionWrapper,
     ) -> Result<Vec<Field>, CusError> {
         let value: Vec<Value> = self
             .execute_with(redis::cmd("config").arg("get").arg(pattern), conn)
@@ -60,38 +88,52 @@ impl Manager {
     }
 
     pub async fn get_version(&self, id: u32) -> Result<String, CusError> {
-        if let Some(conn) = self.map.lock().await.get_mut(&id) {
-            return self.get_version_with(conn).await;
-        }
-        Err(CusError::connection_not_found())
+        let conn = self.get(id).await?;
+        self.get_version_with(&conn).await
     }
 
     // get redis server version
-    pub async fn get_version_with(&self, conn: &mut ConnectionWrapper) -> Result<String, CusError> {
-        if let Some(version) = &conn.version {
-            Ok(version.clone())
-        } else {
-            let info = self.get_info_with(conn).await?;
-            for x in info.keys() {
-                if let Some(fields) = info.get(x) {
-                    if let Some(version) = fields.get("redis_version") {
-                        conn.version = Some(version.clone());
-                        return Ok(version.clone());
-                    }
+    pub async fn get_version_with(&self, conn: &ConnectionWrapper) -> Result<String, CusError> {
+        if let Some(version) = conn.version.lock().unwrap().clone() {
+            return Ok(version);
+        }
+        let info = self.get_info_with(conn).await?;
+        for x in info.keys() {
+            if let Some(fields) = info.get(x) {
+                if let Some(version) = fields.get("redis_version") {
+                    *conn.version.lock().unwrap() = Some(version.clone());
+                    return Ok(version.clone());
                 }
             }
-            Err(CusError::reopen())
         }
+        Err(CusError::reopen())
+    }
+
+    // the commands of the server, loaded once per version
+    pub async fn get_docs(&self, id: u32) -> Result<Arc<CommandDocs>, CusError> {
+        let conn = self.get(id).await?;
+        self.get_docs_with(&conn).await
+    }
+
+    pub async fn get_docs_with(
+        &self,
+        conn: &ConnectionWrapper,
+    ) -> Result<Arc<CommandDocs>, CusError> {
+        let version = self.get_version_with(conn).await?;
+        if let Some(docs) = self.docs.read().await.get(&version) {
+            return Ok(docs.clone());
+        }
+        let docs = Arc::new(CommandDocs::load(conn, &version).await?);
+        self.docs.write().await.insert(version, docs.clone());
+        Ok(docs)
     }
 
     pub async fn get_info(
         &self,
         id: u32,
     ) -> Result<HashMap<String, HashMap<String, String>>, CusError> {
-        if let Some(conn) = self.map.lock().await.get_mut(&id) {
-            return self.get_info_with(conn).await;
-        }
-        Err(CusError::connection_not_found())
+        let conn = self.get(id).await?;
+        self.get_info_with(&conn).await
     }
 
     // get the server info
@@ -99,7 +141,7 @@ impl Manager {
     // so for unify, normal server is change to vec, the value is set to vec
     pub async fn get_info_with(
         &self,
-        conn: &mut ConnectionWrapper,
+        conn: &ConnectionWrapper,
     ) -> Result<HashMap<String, HashMap<String, String>>, CusError> {
         let v = self.execute_with(&mut redis::cmd("info"), conn).await?;
         let format_fn = |str_value: String| {
@@ -143,113 +185,165 @@ impl Manager {
 
     // get cluster server nodes
     pub async fn get_nodes(&self, id: u32) -> Result<Vec<Node>, CusError> {
-        if let Some(conn) = self.map.lock().await.get_mut(&id) {
-            return self.get_nodes_with(conn).await;
-        }
-        Err(CusError::reopen())
+        let conn = self.get(id).await.map_err(|_| CusError::reopen())?;
+        self.get_nodes_with(&conn).await
     }
 
     // get cluster server nodes
-    pub async fn get_nodes_with(
-        &self,
-        wrapper: &mut ConnectionWrapper,
-    ) -> Result<Vec<Node>, CusError> {
+    pub async fn get_nodes_with(&self, wrapper: &ConnectionWrapper) -> Result<Vec<Node>, CusError> {
         if !wrapper.model.is_cluster() {
             return Err(CusError::App(String::from("Not a Cluster Server")));
         }
-        if wrapper.nodes.is_empty() {
+        let mut nodes = wrapper.nodes.lock().await;
+        if nodes.is_empty() {
             let params = wrapper.model.get_params();
             let values = self
                 .execute_with(redis::cmd("CLUSTER").arg("NODES"), wrapper)
                 .await?;
             let csv = String::from_redis_value(&values)?;
             let items: Vec<&str> = csv.split("\n").collect();
-            let mut nodes: Vec<Node> = vec![];
             for ss in items {
                 if ss.trim() != "" {
-                    let node = Node::build(ss.to_string(), params.clone());
+                    let node = Node::build(ss, params.clone())?;
                     nodes.push(node)
                 }
             }
-            wrapper.nodes = nodes;
         }
-        Ok(wrapper.nodes.to_vec())
+        Ok(nodes.to_vec())
+    }
+
+    // connect to a cluster node to read
+    // if the connection prefer replica, a healthy replica of the master is used instead
+    pub async fn connect_node(&self, cid: u32, node: Node) -> Result<ConnectionWrapper, CusError> {
+        let mut target = node;
+        if target.is_master() && self.get_read_from_replica(cid).await {
+            let nodes = self.get_nodes(cid).await?;
+            if let Some(replica) = nodes
+                .into_iter()
+                .find(|n| n.is_replica() && !n.is_failed() && n.master.as_ref() == Some(&target.id))
+            {
+                target = replica;
+            }
+        }
+        self.connect_to(target).await
+    }
+
+    // connect to the cluster node itself, the replica is set to READONLY
+    pub async fn connect_to(&self, node: Node) -> Result<ConnectionWrapper, CusError> {
+        let is_replica = node.is_replica();
+        let conn = ConnectionWrapper::build(node).await?;
+        if is_replica {
+            self.execute_with::<String>(&mut redis::cmd("READONLY"), &conn)
+                .await?;
+        }
+        Ok(conn)
+    }
+
+    // the write command to the production connection must be confirmed by the user
+    // the admin reads are known, the others are classified by the flags of COMMAND INFO
+    // the unknown command is treated as a write
+    pub async fn check_write(&self, cmd: &Cmd, conn: &ConnectionWrapper) -> Result<(), CusError> {
+        if !self.need_confirm(conn) || !self.is_write(cmd, conn).await {
+            return Ok(());
+        }
+        Err(CusError::ConfirmRequired)
+    }
+
+    // the command may write, the docs of the server are loaded if it is not known as safe
+    pub async fn is_write(&self, cmd: &Cmd, conn: &ConnectionWrapper) -> bool {
+        if command::is_safe(cmd) {
+            return false;
+        }
+        // boxed, loading the docs executes INFO through the manager
+        let docs = Box::pin(self.get_docs_with(conn)).await.ok();
+        is_write(cmd, docs.as_deref())
+    }
+
+    // the request may write, such as a script of unknown commands
+    pub fn check_confirmed(&self, conn: &ConnectionWrapper) -> Result<(), CusError> {
+        if self.need_confirm(conn) {
+            return Err(CusError::ConfirmRequired);
+        }
+        Ok(())
+    }
+
+    fn need_confirm(&self, conn: &ConnectionWrapper) -> bool {
+        conn.model.params.confirm_write && !WRITE_CONFIRMED.try_with(|c| *c).unwrap_or(false)
     }
 
     // execute redis cmd with connection
     pub async fn execute_with<T>(
         &self,
         cmd: &mut Cmd,
-        conn: &mut ConnectionWrapper,
+        conn: &ConnectionWrapper,
+    ) -> Result<T, CusError>
+    where
+        T: FromRedisValue,
+    {
+        self.execute_db(cmd, conn, None).await
+    }
+
+    // execute redis cmd with the connection of the db
+    pub async fn execute_db<T>(
+        &self,
+        cmd: &mut Cmd,
+        conn: &ConnectionWrapper,
+        db: Option<u8>,
     ) -> Result<T, CusError>
     where
         T: FromRedisValue,
     {
-        let result: Result<(T, Command), (CusError, Command)> = conn.execute::<T>(cmd).await;
+        self.check_write(cmd, conn).await?;
+        let result: Result<(T, Command), (CusError, Command)> = conn.execute::<T>(cmd, db).await;
+        if conn.is_broken() {
+            self.broken.notify_one();
+        }
         match result {
             Ok((value, cmd)) => {
-                if let Some(tx) = self.debug_tx.lock().await.get_mut(0) {
-                    let _ = tx.send(cmd).await;
-                }
+                let _ = self.debug_tx.send(cmd);
                 Ok(value)
             }
             Err((err, cmd)) => {
-                if let Some(tx) = self.debug_tx.lock().await.get_mut(0) {
-                    let _ = tx.send(cmd).await;
-                }
+                let _ = self.debug_tx.send(cmd);
                 Err(err)
             }
         }
     }
+
     // execute redis cmd with cid
     pub async fn execute<T>(&self, cid: u32, cmd: &mut Cmd, db: Option<u8>) -> Result<T, CusError>
     where
         T: FromRedisValue,
     {
-        if let Some(conn) = self.map.lock().await.get_mut(&cid) {
-            if !conn.is_cluster() {
-                if let Some(database) = db {
-                    if database != conn.db {
-                        self
-                            .execute_with::<String>(redis::cmd("select").arg(db), conn)
-                            .await?;
-                        conn.db = database
-                    }
-                }
+        let conn = self.get(cid).await?;
+        match conn.get_state() {
+            ConnectionState::Reconnecting => {
+                return Err(CusError::build("Connection is reconnecting"))
             }
-            return self.execute_with::<T>(cmd, conn).await;
+            ConnectionState::Failed => return Err(CusError::reopen()),
+            ConnectionState::Connected => {}
         }
-        Err(CusError::connection_not_found())
+        self.execute_db::<T>(cmd, &conn, db).await
     }
 
     pub async fn get_is_cluster(&self, cid: u32) -> bool {
-        if let Some(conn) = self.map.lock().await.get_mut(&cid) {
+        if let Some(conn) = self.map.read().await.get(&cid) {
             return conn.is_cluster();
         }
         false
     }
 
-    pub async fn get_sync_conn(&self, cid: u32) -> Result<RedisSyncConnection, CusError> {
-        if let Some(conn) = self.map.lock().await.get_mut(&cid) {
-            return conn.model.get_sync_one().await;
-        }
-        Err(CusError::connection_not_found())
-    }
-
-    pub async fn get_sync_cluster_conn(
-        &self,
-        cid: u32,
-    ) -> Result<RedisSyncClusterConnection, CusError> {
-        if let Some(conn) = self.map.lock().await.get_mut(&cid) {
-            return conn.model.get_sync_cluster_one().await;
+    pub async fn get_read_from_replica(&self, cid: u32) -> bool {
+        if let Some(conn) = self.map.read().await.get(&cid) {
+            return conn.model.params.read_from_replica;
         }
-        Err(CusError::connection_not_found())
+        false
     }
 
     // get connected connections info
     pub async fn get_conns(&self) -> Vec<response::Conn> {
         let mut vec = vec![];
-        for (_, v) in self.map.lock().await.iter() {
+        for (_, v) in self.map.read().await.iter() {
             vec.push(response::Conn {
                 id: v.id.clone(),
                 host: v.model.get_host(),
@@ -263,16 +357,130 @@ impl Manager {
 
     // remove  connected connection
     pub async fn remove(&self, id: u32) {
-        self.map.lock().await.remove(&id);
+        self.map.write().await.remove(&id);
+    }
+
+    pub async fn broken_notified(&self) {
+        self.broken.notified().await
     }
 
-    // set debug tx
-    pub async fn set_tx(&self, tx: Sender<Command>) {
-        self.debug_tx.lock().await.insert(0, tx);
+    // ping the connected connections
+    // the broken ones are marked as reconnecting and returned
+    pub async fn check_health(&self, timeout: Duration) -> Vec<u32> {
+        let conns: Vec<(u32, Arc<ConnectionWrapper>)> = self
+            .map
+            .read()
+            .await
+            .iter()
+            .map(|(id, conn)| (*id, conn.clone()))
+            .collect();
+        let mut broken = vec![];
+        for (id, conn) in conns {
+            if conn.get_state() != ConnectionState::Connected {
+                continue;
+            }
+            if !conn.is_broken() {
+                let cmd = redis::cmd("PING");
+                let ping = conn.execute::<String>(&cmd, None);
+                if !matches!(tokio::time::timeout(timeout, ping).await, Ok(Ok(_))) {
+                    conn.set_broken();
+                }
+            }
+            if conn.is_broken() {
+                conn.set_state(ConnectionState::Reconnecting);
+                broken.push(id);
+            }
+        }
+        broken
+    }
+
+    // rebuild the connection with the same params
+    // the connections of other dbs are rebuilt when used
+    // return false if the connection has been closed
+    pub async fn reconnect(&self, id: u32) -> Result<bool, CusError> {
+        let params = match self.map.read().await.get(&id) {
+            Some(conn) => conn.model.origin.clone(),
+            None => return Ok(false),
+        };
+        let mut conn = ConnectionWrapper::build(params).await?;
+        conn.cid = Some(id);
+        let _ = self.set_name(&conn, CLIENT_NAME.to_string()).await;
+        let mut map = self.map.write().await;
+        match map.get(&id) {
+            Some(old) if old.get_state() == ConnectionState::Reconnecting => {
+                map.insert(id, Arc::new(conn));
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub async fn set_state(&self, id: u32, state: ConnectionState) {
+        if let Some(conn) = self.map.read().await.get(&id) {
+            conn.set_state(state);
+        }
+    }
+
+    // every log window has its own receiver
+    // the command run outside the manager, such as by the terminal, is logged as well
+    pub fn log(&self, cmd: Command) {
+        let _ = self.debug_tx.send(cmd);
+    }
+
+    pub fn subscribe_debug(&self) -> broadcast::Receiver<Command> {
+        self.debug_tx.subscribe()
+    }
+}
+
+// the unknown command is treated as a write
+fn is_write(cmd: &Cmd, docs: Option<&CommandDocs>) -> bool {
+    if command::is_safe(cmd) {
+        return false;
+    }
+    let (name, sub) = command::get_name(cmd);
+    docs.and_then(|d| d.is_write(&name, sub.as_deref()))
+        .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::docs::CommandDoc;
+
+    fn docs() -> CommandDocs {
+        let mut docs = CommandDocs::default();
+        for (name, flags) in [
+            ("eval", vec!["noscript", "stale", "may_replicate"]),
+            ("eval_ro", vec!["readonly", "noscript", "stale"]),
+            ("publish", vec!["pubsub", "fast", "may_replicate"]),
+            ("ping", vec!["fast"]),
+            ("set", vec!["write", "denyoom"]),
+        ] {
+            let doc = CommandDoc {
+                name: name.to_string(),
+                flags: flags.into_iter().map(String::from).collect(),
+                ..Default::default()
+            };
+            docs.commands.insert(name.to_string(), doc);
+        }
+        docs
     }
 
-    // remove debug tx
-    pub async fn remove_tx(&self) {
-        self.debug_tx.lock().await.remove(0);
+    #[test]
+    fn eval_needs_confirm() {
+        let docs = docs();
+        let eval = redis::cmd("EVAL").arg("return 1").arg(0).clone();
+        assert!(is_write(&eval, Some(&docs)));
+        let eval_ro = redis::cmd("EVAL_RO").arg("return 1").arg(0).clone();
+        assert!(!is_write(&eval_ro, Some(&docs)));
+        assert!(is_write(
+            redis::cmd("PUBLISH").arg("c").arg("m"),
+            Some(&docs)
+        ));
+        assert!(is_write(redis::cmd("SET").arg("k").arg("v"), Some(&docs)));
+        assert!(!is_write(&redis::cmd("PING"), Some(&docs)));
+        // the commands not in the docs
+        assert!(is_write(&redis::cmd("FCALL"), Some(&docs)));
+        assert!(is_write(redis::cmd("DEL").arg("k"), None));
     }
 }
@@ -1,13 +1,21 @@
 use redis::{FromRedisValue, RedisResult, Value as RedisValue};
+use serde::ser::SerializeStruct;
+mod command;
 mod conn;
+mod docs;
 mod event;
 mod manager;
 mod node;
+mod supervisor;
 
-pub use conn::{Connectable, ConnectedParam, Connection, ConnectionParams, ConnectionWrapper};
+pub use conn::{
+    Connectable, ConnectedParam, Connection, ConnectionParams, ConnectionWrapper, CLIENT_NAME,
+};
+pub use docs::CommandDocs;
 pub use event::EventManager;
-pub use manager::Manager;
-pub use node::Node;
+pub use manager::{Manager, WRITE_CONFIRMED};
+pub use node::{build_shards, check_coverage, Node, Shard, SlotCoverage};
+pub use supervisor::{supervise, ConnectionState};
 #[derive(Clone, Debug)]
 pub enum CValue {
     Str(String),
@@ -17,6 +25,19 @@ pub enum CValue {
     Nil,
     Bool(bool),
     Map(Vec<(CValue, CValue)>),
+    Set(Vec<CValue>),
+    // RESP3 attribute, the attributes are the metadata of data
+    Attribute {
+        data: Box<CValue>,
+        attributes: Vec<(CValue, CValue)>,
+    },
+    // RESP3 push message, such as pubsub message and invalidation of client tracking
+    Push {
+        kind: String,
+        data: Vec<CValue>,
+    },
+    // the full error message, such as `ERR unknown command`
+    Error(String),
 }
 impl FromRedisValue for CValue {
     fn from_redis_value(v: &RedisValue) -> RedisResult<Self> {
@@ -37,13 +58,26 @@ impl serde::Serialize for CValue {
             CValue::Float(v) => v.serialize(serializer),
             CValue::Bool(v) => v.serialize(serializer),
             CValue::Map(v) => v.serialize(serializer),
+            CValue::Set(v) => v.serialize(serializer),
+            CValue::Attribute { data, attributes } => {
+                let mut s = serializer.serialize_struct("attribute", 2)?;
+                s.serialize_field("data", data)?;
+                s.serialize_field("attributes", attributes)?;
+                s.end()
+            }
+            CValue::Push { kind, data } => {
+                let mut s = serializer.serialize_struct("push", 2)?;
+                s.serialize_field("kind", kind)?;
+                s.serialize_field("data", data)?;
+                s.end()
+            }
+            CValue::Error(v) => serializer.serialize_str(v),
         }
     }
 }
 
 impl CValue {
     pub fn build(v: RedisValue) -> CValue {
-        println!("{:?}", v);
         match v {
             RedisValue::Okay => Self::Str("Ok".to_string()),
             RedisValue::BulkString(s) => {
@@ -56,40 +90,38 @@ impl CValue {
                     }
                 }
             }
-            RedisValue::Array(v) => {
-                let mut vec = vec![];
-                for x in v {
-                    vec.push(Self::build(x));
-                }
-                Self::Vec(vec)
-            }
+            RedisValue::Array(v) => Self::Vec(Self::build_vec(v)),
             RedisValue::Nil => Self::Nil,
             RedisValue::SimpleString(s) => Self::Str(s),
             RedisValue::Int(s) => Self::Int(s),
-            RedisValue::Map(v) => {
-                let mut vec = vec![];
-                for (x, y) in v {
-                    vec.push((Self::build(x), Self::build(y)));
-                }
-                Self::Map(vec)
-            }
-            // RedisValue::Attribute { data, attributes } => todo!(),
-            RedisValue::Set(v) => {
-                let mut vec = vec![];
-                for x in v {
-                    vec.push(Self::build(x));
-                }
-                Self::Vec(vec)
-            }
+            RedisValue::Map(v) => Self::Map(Self::build_map(v)),
+            RedisValue::Attribute { data, attributes } => Self::Attribute {
+                data: Box::new(Self::build(*data)),
+                attributes: Self::build_map(attributes),
+            },
+            RedisValue::Set(v) => Self::Set(Self::build_vec(v)),
             RedisValue::Double(v) => Self::Float(v),
             RedisValue::Boolean(v) => Self::Bool(v),
             RedisValue::VerbatimString { format: _format, text } => Self::Str(text),
             RedisValue::BigNumber(big_int) => Self::Str(big_int.to_string()),
-            // RedisValue::Push { kind, data } => todo!(),
-            RedisValue::ServerError(server_error) => Self::Str(server_error.code().to_string()),
-            _ => {
-                CValue::Nil
-            }
+            RedisValue::Push { kind, data } => Self::Push {
+                kind: kind.to_string(),
+                data: Self::build_vec(data),
+            },
+            RedisValue::ServerError(server_error) => match server_error.details() {
+                Some(detail) => Self::Error(format!("{} {}", server_error.code(), detail)),
+                None => Self::Error(server_error.code().to_string()),
+            },
         }
     }
+
+    fn build_vec(v: Vec<RedisValue>) -> Vec<CValue> {
+        v.into_iter().map(Self::build).collect()
+    }
+
+    fn build_map(v: Vec<(RedisValue, RedisValue)>) -> Vec<(CValue, CValue)> {
+        v.into_iter()
+            .map(|(x, y)| (Self::build(x), Self::build(y)))
+            .collect()
+    }
 }
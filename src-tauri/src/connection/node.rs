@@ -1,19 +1,77 @@
 use crate::connection::{Connectable, ConnectionParams};
+use crate::err::CusError;
 
 use serde::ser::{Serialize as CusSerialize, SerializeStruct, Serializer};
+use serde::Serialize;
+
+// the total slots of a redis cluster
+pub const CLUSTER_SLOTS: u16 = 16384;
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct SlotRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+// a slot in migrating/importing state
+// node is the target node id when migrating, the source node id when importing
+#[derive(Clone, Debug, Serialize)]
+pub struct SlotTransfer {
+    pub slot: u16,
+    pub node: String,
+}
+
+#[derive(Clone, Debug, Serialize, Default)]
+pub struct NodeFlags {
+    pub myself: bool,
+    pub master: bool,
+    pub replica: bool,
+    pub pfail: bool,
+    pub fail: bool,
+    pub handshake: bool,
+    pub noaddr: bool,
+    pub nofailover: bool,
+}
+
+impl NodeFlags {
+    pub fn build(s: &str) -> Self {
+        let mut flags = Self::default();
+        for f in s.split(",") {
+            match f {
+                "myself" => flags.myself = true,
+                "master" => flags.master = true,
+                "slave" | "replica" => flags.replica = true,
+                "fail?" => flags.pfail = true,
+                "fail" => flags.fail = true,
+                "handshake" => flags.handshake = true,
+                "noaddr" => flags.noaddr = true,
+                "nofailover" => flags.nofailover = true,
+                _ => {}
+            }
+        }
+        flags
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Node {
     pub id: String,
     pub host: String,
     pub port: u16,
+    pub cport: Option<u16>,
+    pub hostname: Option<String>,
+    pub shard_id: Option<String>,
     pub flags: String,
-    pub master: String,
+    pub flag_set: NodeFlags,
+    pub master: Option<String>,
     pub ping_sent: i64,
     pub pong_recv: i64,
-    pub config_epoch: String,
+    pub config_epoch: u64,
     pub link_state: String,
     pub slot: String,
+    pub slots: Vec<SlotRange>,
+    pub migrating: Vec<SlotTransfer>,
+    pub importing: Vec<SlotTransfer>,
     pub params: ConnectionParams,
 }
 
@@ -28,42 +86,80 @@ impl CusSerialize for Node {
     where
         S: Serializer,
     {
-        let mut node = serializer.serialize_struct("node", 10)?;
+        let mut node = serializer.serialize_struct("node", 18)?;
         node.serialize_field("id", &self.id)?;
         node.serialize_field("host", &self.host)?;
         node.serialize_field("port", &self.port)?;
+        node.serialize_field("cport", &self.cport)?;
+        node.serialize_field("hostname", &self.hostname)?;
+        node.serialize_field("shard_id", &self.shard_id)?;
         node.serialize_field("flags", &self.flags)?;
+        node.serialize_field("flag_set", &self.flag_set)?;
         node.serialize_field("master", &self.master)?;
         node.serialize_field("ping_sent", &self.ping_sent)?;
         node.serialize_field("pong_recv", &self.pong_recv)?;
         node.serialize_field("config_epoch", &self.config_epoch)?;
         node.serialize_field("link_state", &self.link_state)?;
         node.serialize_field("slot", &self.slot)?;
+        node.serialize_field("slots", &self.slots)?;
+        node.serialize_field("migrating", &self.migrating)?;
+        node.serialize_field("importing", &self.importing)?;
         node.end()
     }
 }
 
 impl Node {
-    pub fn build(s: String, params: ConnectionParams) -> Self {
-        let mut v: Vec<&str> = s.split(" ").collect();
-        let get_fn = |v: &mut Vec<&str>, index: usize| -> String {
-            if v.len() > index {
-                return String::from(v.remove(index));
+    // build node from a line of `CLUSTER NODES`
+    // <id> <ip:port@cport[,hostname[,aux=value]*]> <flags> <master> <ping-sent> <pong-recv> <config-epoch> <link-state> <slot> ... <slot>
+    pub fn build(s: &str, params: ConnectionParams) -> Result<Self, CusError> {
+        let v: Vec<&str> = s.split_whitespace().collect();
+        if v.len() < 8 {
+            return Err(CusError::App(format!("invalid cluster node: {}", s)));
+        }
+        let mut addr_parts = v[1].split(",");
+        let addr = addr_parts.next().unwrap_or_default();
+        let mut hostname = None;
+        let mut shard_id = None;
+        for (i, part) in addr_parts.enumerate() {
+            match part.split_once("=") {
+                Some(("shard-id", value)) => shard_id = Some(value.to_string()),
+                Some(_) => {}
+                None => {
+                    if i == 0 && !part.is_empty() {
+                        hostname = Some(part.to_string())
+                    }
+                }
             }
-            "".to_string()
+        }
+        let (host_port, cport) = match addr.split_once("@") {
+            Some((host_port, cport)) => (host_port, Some(Self::parse_port(cport, s)?)),
+            None => (addr, None),
         };
-        let mut host = get_fn(&mut v, 1);
-        let mut port = 0;
-        if let Some(u) = host.find("@") {
-            host = host[0..u].to_string();
-            if let Some(u) = host.find(":") {
-                let port_str = host[u + 1..].to_string();
-                host = host[0..u].to_string();
-                port = port_str.parse().unwrap()
+        // use the last colon, ipv6 address contains colon too
+        let (host, port) = match host_port.rfind(":") {
+            Some(u) => (
+                host_port[0..u].to_string(),
+                Self::parse_port(&host_port[u + 1..], s)?,
+            ),
+            None => {
+                return Err(CusError::App(format!(
+                    "invalid cluster node address: {}",
+                    s
+                )))
             }
+        };
+
+        let mut slots = vec![];
+        let mut migrating = vec![];
+        let mut importing = vec![];
+        for token in &v[8..] {
+            Self::parse_slot(token, &mut slots, &mut migrating, &mut importing)
+                .ok_or_else(|| CusError::App(format!("invalid cluster slot: {}", token)))?;
         }
+
         let mut p: ConnectionParams = params.clone();
         p.is_cluster = false;
+        p.replica_params = None;
         p.redis_params.tcp_host = host.clone();
         p.redis_params.tcp_port = port;
         if let Some(mut ssh) = p.ssh_params {
@@ -71,19 +167,188 @@ impl Node {
             ssh.target_port = port;
             p.ssh_params = Some(ssh)
         }
-        let node = Self {
-            id: get_fn(&mut v, 0),
-            host: host.clone(),
+        if let Some(mut proxy) = p.proxy_params {
+            proxy.target_host = host.clone();
+            proxy.target_port = port;
+            p.proxy_params = Some(proxy)
+        }
+        let master = match v[3] {
+            "-" => None,
+            m => Some(m.to_string()),
+        };
+        Ok(Self {
+            id: v[0].to_string(),
+            host,
             port,
-            flags: get_fn(&mut v, 0),
-            master: get_fn(&mut v, 0),
-            ping_sent: get_fn(&mut v, 0).parse::<i64>().unwrap_or_default(),
-            pong_recv: get_fn(&mut v, 0).parse::<i64>().unwrap_or_default(),
-            config_epoch: get_fn(&mut v, 0),
-            link_state: get_fn(&mut v, 0),
-            slot: get_fn(&mut v, 0),
+            cport,
+            hostname,
+            shard_id,
+            flags: v[2].to_string(),
+            flag_set: NodeFlags::build(v[2]),
+            master,
+            ping_sent: v[4].parse::<i64>().unwrap_or_default(),
+            pong_recv: v[5].parse::<i64>().unwrap_or_default(),
+            config_epoch: v[6].parse::<u64>().unwrap_or_default(),
+            link_state: v[7].to_string(),
+            slot: v[8..].join(" "),
+            slots,
+            migrating,
+            importing,
             params: p,
+        })
+    }
+
+    fn parse_port(s: &str, line: &str) -> Result<u16, CusError> {
+        s.parse::<u16>()
+            .map_err(|_| CusError::App(format!("invalid cluster node port: {}", line)))
+    }
+
+    // slot token is one of:
+    // 5461 / 0-5460 / [93->-<node id>] (migrating) / [93-<-<node id>] (importing)
+    fn parse_slot(
+        token: &str,
+        slots: &mut Vec<SlotRange>,
+        migrating: &mut Vec<SlotTransfer>,
+        importing: &mut Vec<SlotTransfer>,
+    ) -> Option<()> {
+        if let Some(inner) = token.strip_prefix("[").and_then(|t| t.strip_suffix("]")) {
+            if let Some((slot, node)) = inner.split_once("->-") {
+                migrating.push(SlotTransfer {
+                    slot: slot.parse().ok()?,
+                    node: node.to_string(),
+                });
+            } else if let Some((slot, node)) = inner.split_once("-<-") {
+                importing.push(SlotTransfer {
+                    slot: slot.parse().ok()?,
+                    node: node.to_string(),
+                });
+            } else {
+                return None;
+            }
+            return Some(());
+        }
+        let (start, end) = match token.split_once("-") {
+            Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+            None => {
+                let slot = token.parse().ok()?;
+                (slot, slot)
+            }
         };
-        node
+        if start > end || end >= CLUSTER_SLOTS {
+            return None;
+        }
+        slots.push(SlotRange { start, end });
+        Some(())
+    }
+
+    pub fn is_master(&self) -> bool {
+        self.flag_set.master
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.flag_set.replica
+    }
+
+    // the node can not serve request
+    pub fn is_failed(&self) -> bool {
+        self.flag_set.fail || self.flag_set.pfail || self.flag_set.noaddr
+    }
+}
+
+// a master with its replicas
+#[derive(Serialize, Clone, Debug)]
+pub struct Shard {
+    pub master: Node,
+    pub replicas: Vec<Node>,
+}
+
+// group the nodes to master -> replicas tree
+// replica whose master is unknown is ignored
+pub fn build_shards(nodes: &[Node]) -> Vec<Shard> {
+    let mut shards: Vec<Shard> = nodes
+        .iter()
+        .filter(|n| n.is_master())
+        .map(|n| Shard {
+            master: n.clone(),
+            replicas: vec![],
+        })
+        .collect();
+    for n in nodes.iter().filter(|n| n.is_replica()) {
+        if let Some(master_id) = &n.master {
+            if let Some(shard) = shards.iter_mut().find(|s| &s.master.id == master_id) {
+                shard.replicas.push(n.clone())
+            }
+        }
+    }
+    shards.sort_by_key(|s| s.master.slots.first().map(|r| r.start));
+    shards
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SlotCoverage {
+    pub assigned: u32,
+    pub unassigned: Vec<SlotRange>,
+    // assigned to the failed masters only, not served until a failover
+    pub failed: Vec<SlotRange>,
+    pub migrating: Vec<SlotTransfer>,
+    pub importing: Vec<SlotTransfer>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SlotOwner {
+    None,
+    Failed,
+    Healthy,
+}
+
+// check all slots are served by a master
+pub fn check_coverage(nodes: &[Node]) -> SlotCoverage {
+    let mut owners = vec![SlotOwner::None; CLUSTER_SLOTS as usize];
+    let mut migrating = vec![];
+    let mut importing = vec![];
+    for n in nodes.iter().filter(|n| n.is_master()) {
+        let owner = if n.is_failed() {
+            SlotOwner::Failed
+        } else {
+            SlotOwner::Healthy
+        };
+        for r in &n.slots {
+            for s in r.start..=r.end {
+                // a healthy master is kept if the slot is also claimed by a failed one
+                if owners[s as usize] != SlotOwner::Healthy {
+                    owners[s as usize] = owner;
+                }
+            }
+        }
+        if owner == SlotOwner::Healthy {
+            migrating.extend(n.migrating.iter().cloned());
+            importing.extend(n.importing.iter().cloned());
+        }
+    }
+    SlotCoverage {
+        assigned: owners.iter().filter(|o| **o != SlotOwner::None).count() as u32,
+        unassigned: slot_ranges(&owners, SlotOwner::None),
+        failed: slot_ranges(&owners, SlotOwner::Failed),
+        migrating,
+        importing,
+    }
+}
+
+// the consecutive slots of the owner
+fn slot_ranges(owners: &[SlotOwner], owner: SlotOwner) -> Vec<SlotRange> {
+    let mut ranges: Vec<SlotRange> = vec![];
+    for (i, o) in owners.iter().enumerate() {
+        if *o != owner {
+            continue;
+        }
+        let slot = i as u16;
+        match ranges.last_mut() {
+            Some(last) if last.end + 1 == slot => last.end = slot,
+            _ => ranges.push(SlotRange {
+                start: slot,
+                end: slot,
+            }),
+        }
     }
+    ranges
 }
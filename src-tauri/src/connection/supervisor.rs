@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager as _};
+
+use crate::connection::Manager;
+use crate::response::EventResp;
+
+// the interval to ping the opened connections
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_ATTEMPTS: u32 = 10;
+
+pub const STATE_EVENT: &str = "connection-state";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    Connected,
+    Reconnecting,
+    Failed,
+}
+
+#[derive(Serialize, Clone)]
+struct StateChange {
+    cid: u32,
+    state: ConnectionState,
+    attempt: u32,
+    message: Option<String>,
+}
+
+fn emit(app: &AppHandle, cid: u32, state: ConnectionState, attempt: u32, message: Option<String>) {
+    let r = EventResp::new(
+        StateChange {
+            cid,
+            state,
+            attempt,
+            message,
+        },
+        String::from(STATE_EVENT),
+    );
+    if let Ok(s) = serde_json::to_string(&r) {
+        let _ = app.emit(STATE_EVENT, s);
+    }
+}
+
+// watch the opened connections, rebuild the broken one
+pub fn supervise(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let manager = app.state::<Manager>();
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {},
+                _ = manager.broken_notified() => {},
+            }
+            for cid in manager.check_health(PING_TIMEOUT).await {
+                tauri::async_runtime::spawn(reconnect(app.clone(), cid));
+            }
+        }
+    });
+}
+
+// reconnect with exponential backoff
+async fn reconnect(app: AppHandle, cid: u32) {
+    let manager = app.state::<Manager>();
+    let mut backoff = Duration::from_secs(1);
+    let mut message = None;
+    for attempt in 1..=MAX_ATTEMPTS {
+        emit(
+            &app,
+            cid,
+            ConnectionState::Reconnecting,
+            attempt,
+            message.clone(),
+        );
+        match manager.reconnect(cid).await {
+            Ok(true) => {
+                emit(&app, cid, ConnectionState::Connected, attempt, None);
+                return;
+            }
+            // the connection has been closed by user
+            Ok(false) => return,
+            Err(e) => {
+                tracing::warn!(cid, attempt, error = %e, "reconnect failed");
+                message = Some(e.to_string())
+            }
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+    tracing::error!(cid, "connection failed");
+    manager.set_state(cid, ConnectionState::Failed).await;
+    emit(&app, cid, ConnectionState::Failed, MAX_ATTEMPTS, message);
+}
@@ -1,4 +1,5 @@
 use std::string::FromUtf8Error;
+use std::time::Duration;
 
 use thiserror;
 
@@ -11,16 +12,21 @@ pub enum CusError {
     #[error(transparent)]
     Sqlite(#[from] rusqlite::Error),
     #[error(transparent)]
-    Reqwest(#[from] reqwest::Error),
-    #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error(transparent)]
     Redis(#[from] redis::RedisError),
     #[error(transparent)]
     Serde(#[from] serde_json::Error),
     #[error(transparent)]
+    Ssh(#[from] async_ssh2_lite::ssh2::Error),
+    #[error(transparent)]
     Utf8(#[from] FromUtf8Error),
     #[error("{0}")]
+    Timeout(String),
+    // the frontend asks the user and resends with confirmed
+    #[error("CONFIRM_REQUIRED")]
+    ConfirmRequired,
+    #[error("{0}")]
     App(String),
 }
 
@@ -37,6 +43,15 @@ impl CusError {
     pub fn key_not_exists() -> Self {
         Self::App(String::from("Key not exists"))
     }
+    pub fn connect_timeout(d: Duration) -> Self {
+        Self::Timeout(format!("Connect timeout after {}s", d.as_secs()))
+    }
+    pub fn response_timeout(d: Duration) -> Self {
+        Self::Timeout(format!("Response timeout after {}s", d.as_secs()))
+    }
+    pub fn ssh_timeout(d: Duration) -> Self {
+        Self::Timeout(format!("SSH handshake timeout after {}s", d.as_secs()))
+    }
 }
 
 // we must manually implement serde::Serialize
@@ -2,8 +2,13 @@
 mod connection;
 mod err;
 mod key;
+mod logger;
 mod model;
+mod monitor;
+mod php;
+mod proxy;
 mod pubsub;
+mod redact;
 mod request;
 mod response;
 mod route;
@@ -13,6 +18,7 @@ mod utils;
 use pubsub::PubsubManager;
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logger::init();
     sqlite::init();
     tauri::Builder::default()
         .plugin(tauri_plugin_clipboard_manager::init())
@@ -23,6 +29,10 @@ pub fn run() {
         .manage(PubsubManager::new())
         .manage(connection::Manager::new())
         .manage(connection::EventManager::new())
+        .setup(|app| {
+            connection::supervise(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![route::dispatch])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,146 @@
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{fmt, prelude::*, reload, EnvFilter, Registry};
+
+use crate::{err::CusError, sqlite};
+
+const LOG_DIR: &str = "logs";
+const LOG_PREFIX: &str = "backend";
+const LOG_SUFFIX: &str = "log";
+// the daily files kept
+const MAX_FILES: usize = 7;
+// the saved level, changed by logs/level/set
+const LEVEL_FILE: &str = "log_level";
+// the directives override the saved level, such as RDS_LOG=debug,redis=trace
+const LEVEL_ENV: &str = "RDS_LOG";
+const DEFAULT_LEVEL: &str = "info";
+const LEVELS: [&str; 5] = ["trace", "debug", "info", "warn", "error"];
+
+// the writer thread flushes the file until the guard dropped
+static GUARD: OnceCell<WorkerGuard> = OnceCell::new();
+static FILTER: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
+fn log_dir() -> String {
+    format!("{}/{}", sqlite::get_data_dir(), LOG_DIR)
+}
+
+fn level_path() -> String {
+    format!("{}/{}", sqlite::get_data_dir(), LEVEL_FILE)
+}
+
+// the logs of the app at the level, the dependencies only warn
+fn filter(level: &str) -> Result<EnvFilter, CusError> {
+    if !LEVELS.contains(&level) {
+        return Err(CusError::App(format!("Invalid log level {}", level)));
+    }
+    EnvFilter::try_new(format!("warn,{}={}", env!("CARGO_CRATE_NAME"), level))
+        .map_err(|e| CusError::App(e.to_string()))
+}
+
+// the json lines are written to a daily file in the data dir
+// the logs are printed to the terminal as well in the debug build
+pub fn init() {
+    let env_filter = match std::env::var(LEVEL_ENV) {
+        Ok(directives) => EnvFilter::new(directives),
+        Err(_) => filter(&level()).unwrap_or_else(|_| filter(DEFAULT_LEVEL).unwrap()),
+    };
+    let (env_filter, handle) = reload::Layer::new(env_filter);
+    let file_layer = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix(LOG_SUFFIX)
+        .max_log_files(MAX_FILES)
+        .build(log_dir())
+        .ok()
+        .map(|appender| {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let _ = GUARD.set(guard);
+            fmt::layer().json().with_writer(writer)
+        });
+    let stdout_layer = cfg!(debug_assertions).then(fmt::layer);
+    let _ = FILTER.set(handle);
+    let _ = tracing_subscriber::registry()
+        .with(env_filter)
+        .with(file_layer)
+        .with(stdout_layer)
+        .try_init();
+}
+
+pub fn level() -> String {
+    fs::read_to_string(level_path())
+        .map(|s| s.trim().to_string())
+        .unwrap_or(DEFAULT_LEVEL.to_string())
+}
+
+// change the level without restart, it is kept for the next start
+pub fn set_level(level: &str) -> Result<(), CusError> {
+    let level = level.to_lowercase();
+    let env_filter = filter(&level)?;
+    if let Some(handle) = FILTER.get() {
+        handle
+            .reload(env_filter)
+            .map_err(|e| CusError::App(e.to_string()))?;
+    }
+    fs::write(level_path(), &level)?;
+    tracing::info!(level, "log level changed");
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct RecentArgs {
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    // the minimum level, all levels if none
+    pub level: Option<String>,
+}
+
+fn default_limit() -> usize {
+    500
+}
+
+fn rank(level: &str) -> usize {
+    LEVELS
+        .iter()
+        .position(|l| l.eq_ignore_ascii_case(level))
+        .unwrap_or_default()
+}
+
+// the latest logs of the files, oldest first, used by the bug report
+pub fn recent(args: &RecentArgs) -> Result<Vec<serde_json::Value>, CusError> {
+    let min = args.level.as_deref().map(rank).unwrap_or_default();
+    let mut files: Vec<_> = fs::read_dir(log_dir())?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(LOG_PREFIX))
+        })
+        .collect();
+    // the date in the name sorts the files
+    files.sort();
+    let mut result: Vec<serde_json::Value> = vec![];
+    for path in files.iter().rev() {
+        let mut entries = vec![];
+        for line in BufReader::new(fs::File::open(path)?).lines() {
+            let Ok(entry) = serde_json::from_str::<serde_json::Value>(&line?) else {
+                continue;
+            };
+            let level = entry["level"].as_str().unwrap_or_default();
+            if rank(level) >= min {
+                entries.push(entry);
+            }
+        }
+        entries.append(&mut result);
+        result = entries;
+        if result.len() >= args.limit {
+            break;
+        }
+    }
+    let skip = result.len().saturating_sub(args.limit);
+    Ok(result.split_off(skip))
+}
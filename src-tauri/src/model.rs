@@ -5,11 +5,14 @@ use redis::{FromRedisValue, Value as RedisValue};
 use serde::Serialize;
 use std::fmt::Debug;
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct Command {
     pub id: String,
+    // none for the connections not opened by the manager
+    pub connection_id: Option<u32>,
     pub cmd: String,
     pub response: CValue,
+    pub success: bool,
     pub host: String,
     pub created_at: String,
     pub duration: i64,
@@ -0,0 +1,440 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// a line of MONITOR
+// 1339518083.107412 [0 127.0.0.1:60866] "keys" "*"
+// 1339518087.877697 [0 lua] "set" "foo" "bar"
+// 1339518090.422321 [0 unix:/tmp/redis.sock] "get" "foo"
+#[derive(Serialize, Debug, Clone)]
+pub struct MonitorLine {
+    pub timestamp: f64,
+    pub db: u8,
+    pub client: String,
+    // tcp, unix or lua
+    pub client_type: String,
+    // the lowercase command name
+    pub command: String,
+    pub args: Vec<String>,
+    pub raw: String,
+}
+
+impl MonitorLine {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let (timestamp, rest) = raw.split_once(' ')?;
+        let rest = rest.strip_prefix('[')?;
+        let (client_part, rest) = rest.split_once("] ")?;
+        let (db, client) = client_part.split_once(' ')?;
+        let client_type = if client == "lua" {
+            "lua"
+        } else if client.starts_with("unix:") {
+            "unix"
+        } else {
+            "tcp"
+        };
+        let args = parse_args(rest);
+        Some(Self {
+            timestamp: timestamp.parse().ok()?,
+            db: db.parse().ok()?,
+            client: client.to_string(),
+            client_type: client_type.to_string(),
+            command: args.first()?.to_lowercase(),
+            args,
+            raw: raw.to_string(),
+        })
+    }
+
+    // the first argument is treated as the key
+    pub fn key(&self) -> Option<&str> {
+        self.args.get(1).map(|s| s.as_str())
+    }
+}
+
+// the arguments are quoted and escaped like sdscatrepr
+fn parse_args(s: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue;
+        }
+        let mut arg = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => arg.push('\n'),
+                    Some('r') => arg.push('\r'),
+                    Some('t') => arg.push('\t'),
+                    Some('a') => arg.push('\x07'),
+                    Some('b') => arg.push('\x08'),
+                    Some('x') => {
+                        let hex: String = chars.by_ref().take(2).collect();
+                        match u8::from_str_radix(&hex, 16) {
+                            Ok(b) if b.is_ascii() => arg.push(b as char),
+                            // keep the escaped form of the binary byte
+                            _ => arg.push_str(&format!("\\x{}", hex)),
+                        }
+                    }
+                    Some(c) => arg.push(c),
+                    None => {}
+                },
+                c => arg.push(c),
+            }
+        }
+        args.push(arg);
+    }
+    args
+}
+
+// redis style glob: * ? [abc] [^a-z] and \ escape
+pub fn glob_match(pattern: &str, s: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    glob_match_chars(&p, &s)
+}
+
+// iterative, the last star is backtracked to, so the time is linear to the stars
+fn glob_match_chars(p: &[char], s: &[char]) -> bool {
+    let (mut pi, mut si) = (0, 0);
+    // the pattern after the last star and the position of the text it is tried at
+    let mut star: Option<(usize, usize)> = None;
+    while si < s.len() {
+        if p.get(pi) == Some(&'*') {
+            pi += 1;
+            star = Some((pi, si));
+            continue;
+        }
+        if let Some(next) = match_one(p, pi, s[si]) {
+            pi = next;
+            si += 1;
+            continue;
+        }
+        // the star takes one more char
+        match star {
+            Some((sp, ss)) => {
+                pi = sp;
+                si = ss + 1;
+                star = Some((sp, si));
+            }
+            None => return false,
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+// the pattern index after the element at i, if the element matches the char
+fn match_one(p: &[char], i: usize, c: char) -> Option<usize> {
+    match p.get(i)? {
+        '?' => Some(i + 1),
+        '[' => {
+            let mut i = i + 1;
+            let not = p.get(i) == Some(&'^');
+            if not {
+                i += 1;
+            }
+            let mut matched = false;
+            while i < p.len() && p[i] != ']' {
+                if p[i] == '\\' && i + 1 < p.len() {
+                    i += 1;
+                    matched |= p[i] == c;
+                } else if i + 2 < p.len() && p[i + 1] == '-' && p[i + 2] != ']' {
+                    matched |= p[i] <= c && c <= p[i + 2];
+                    i += 2;
+                } else {
+                    matched |= p[i] == c;
+                }
+                i += 1;
+            }
+            // the unclosed bracket matches nothing
+            (i < p.len() && matched != not).then_some(i + 1)
+        }
+        '\\' if i + 1 < p.len() => (p[i + 1] == c).then_some(i + 2),
+        x => (*x == c).then_some(i + 1),
+    }
+}
+
+// the lines not matched are dropped in the task, before emitted to the window
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct MonitorFilter {
+    // the command names, any matched
+    #[serde(default)]
+    pub commands: Vec<String>,
+    // the glob of the key
+    pub key: Option<String>,
+    // the prefix of the client address, such as 127.0.0.1 or lua
+    pub client: Option<String>,
+    pub db: Option<u8>,
+    // the probability to emit a matched line, 0 to 1
+    pub sample_rate: Option<f64>,
+    // the max lines emitted per second
+    pub max_per_second: Option<u32>,
+    // emit the top commands and keys every n seconds
+    pub stats_interval: Option<u64>,
+    #[serde(default = "default_top")]
+    pub top: usize,
+}
+
+fn default_top() -> usize {
+    10
+}
+
+impl MonitorFilter {
+    // any filter of the line set, the sampling and the rate limit are not filters
+    pub fn is_active(&self) -> bool {
+        !self.commands.is_empty()
+            || self.key.is_some()
+            || self.client.is_some()
+            || self.db.is_some()
+    }
+
+    pub fn matches(&self, line: &MonitorLine) -> bool {
+        if !self.commands.is_empty()
+            && !self
+                .commands
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(&line.command))
+        {
+            return false;
+        }
+        if let Some(key) = &self.key {
+            match line.key() {
+                Some(k) if glob_match(key, k) => {}
+                _ => return false,
+            }
+        }
+        if let Some(client) = &self.client {
+            if !line.client.starts_with(client.as_str()) {
+                return false;
+            }
+        }
+        if let Some(db) = self.db {
+            if line.db != db {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Counter {
+    pub name: String,
+    pub count: u64,
+}
+
+// the aggregation of the matched lines in the last interval
+#[derive(Serialize, Debug)]
+pub struct MonitorStats {
+    pub seconds: u64,
+    pub total: u64,
+    pub top_commands: Vec<Counter>,
+    pub top_keys: Vec<Counter>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum MonitorEvent {
+    Line(MonitorLine),
+    // the line not parsed, only emitted without filters
+    Raw { raw: String },
+    Stats(MonitorStats),
+}
+
+// apply the filter, the sampling and the rate limit, count the matched lines
+pub struct MonitorState {
+    filter: MonitorFilter,
+    // the start and the emitted count of the current second
+    second: Instant,
+    emitted: u32,
+    commands: HashMap<String, u64>,
+    keys: HashMap<String, u64>,
+    total: u64,
+    interval_start: Instant,
+}
+
+impl MonitorState {
+    pub fn new(filter: MonitorFilter) -> Self {
+        Self {
+            filter,
+            second: Instant::now(),
+            emitted: 0,
+            commands: HashMap::new(),
+            keys: HashMap::new(),
+            total: 0,
+            interval_start: Instant::now(),
+        }
+    }
+
+    pub fn stats_interval(&self) -> Option<Duration> {
+        self.filter
+            .stats_interval
+            .filter(|s| *s > 0)
+            .map(Duration::from_secs)
+    }
+
+    // the line to emit, none if it is filtered, sampled out or rate limited
+    pub fn accept(&mut self, line: MonitorLine) -> Option<MonitorLine> {
+        if !self.filter.matches(&line) {
+            return None;
+        }
+        if self.stats_interval().is_some() {
+            self.total += 1;
+            *self.commands.entry(line.command.clone()).or_default() += 1;
+            if let Some(key) = line.key() {
+                *self.keys.entry(key.to_string()).or_default() += 1;
+            }
+        }
+        self.throttle().then_some(line)
+    }
+
+    // the line not parsed can not be filtered, it is only emitted without filters
+    pub fn accept_raw(&mut self, raw: String) -> Option<String> {
+        if self.filter.is_active() {
+            return None;
+        }
+        self.throttle().then_some(raw)
+    }
+
+    // the sampling and the rate limit
+    fn throttle(&mut self) -> bool {
+        if let Some(rate) = self.filter.sample_rate {
+            if rand::thread_rng().gen::<f64>() >= rate {
+                return false;
+            }
+        }
+        if let Some(max) = self.filter.max_per_second {
+            if self.second.elapsed() >= Duration::from_secs(1) {
+                self.second = Instant::now();
+                self.emitted = 0;
+            }
+            if self.emitted >= max {
+                return false;
+            }
+            self.emitted += 1;
+        }
+        true
+    }
+
+    // take the counters of the interval
+    pub fn take_stats(&mut self) -> MonitorStats {
+        let stats = MonitorStats {
+            seconds: self.interval_start.elapsed().as_secs(),
+            total: self.total,
+            top_commands: top(std::mem::take(&mut self.commands), self.filter.top),
+            top_keys: top(std::mem::take(&mut self.keys), self.filter.top),
+        };
+        self.total = 0;
+        self.interval_start = Instant::now();
+        stats
+    }
+}
+
+fn top(counter: HashMap<String, u64>, n: usize) -> Vec<Counter> {
+    let mut v: Vec<Counter> = counter
+        .into_iter()
+        .map(|(name, count)| Counter { name, count })
+        .collect();
+    v.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    v.truncate(n);
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line() {
+        let line =
+            MonitorLine::parse(r#"1339518083.107412 [0 127.0.0.1:60866] "keys" "*""#).unwrap();
+        assert_eq!(line.db, 0);
+        assert_eq!(line.client, "127.0.0.1:60866");
+        assert_eq!(line.client_type, "tcp");
+        assert_eq!(line.command, "keys");
+        assert_eq!(line.args, vec!["keys", "*"]);
+
+        let line = MonitorLine::parse(r#"1339518087.877697 [3 lua] "SET" "foo" "bar""#).unwrap();
+        assert_eq!(line.db, 3);
+        assert_eq!(line.client_type, "lua");
+        assert_eq!(line.command, "set");
+        assert_eq!(line.key(), Some("foo"));
+
+        let line = MonitorLine::parse(r#"1.0 [0 unix:/tmp/redis.sock] "get" "foo""#).unwrap();
+        assert_eq!(line.client_type, "unix");
+    }
+
+    #[test]
+    fn parse_escaped() {
+        let line = MonitorLine::parse(
+            r#"1.0 [0 127.0.0.1:1] "set" "say \"hi\"" "a b\\c" "\n\t" "\x41\xff""#,
+        )
+        .unwrap();
+        assert_eq!(line.args[1], r#"say "hi""#);
+        assert_eq!(line.args[2], r"a b\c");
+        assert_eq!(line.args[3], "\n\t");
+        // the binary byte keeps the escaped form
+        assert_eq!(line.args[4], r"A\xff");
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(MonitorLine::parse("OK").is_none());
+        assert!(MonitorLine::parse(r#"1.0 0 127.0.0.1:1 "get" "a""#).is_none());
+        assert!(MonitorLine::parse(r#"x [0 127.0.0.1:1] "get""#).is_none());
+        assert!(MonitorLine::parse("1.0 [0 127.0.0.1:1] ").is_none());
+    }
+
+    #[test]
+    fn glob() {
+        let cases = [
+            ("*", "", true),
+            ("*", "abc", true),
+            ("a*c", "abbbc", true),
+            ("a*c", "abbb", false),
+            ("a?c", "abc", true),
+            ("a?c", "ac", false),
+            ("user:[0-9]*", "user:1a", true),
+            ("user:[^0-9]", "user:1", false),
+            ("[abc]", "b", true),
+            ("[a\\]]", "]", true),
+            ("a\\*", "a*", true),
+            ("a\\*", "ab", false),
+            ("[abc", "a", false),
+            ("*a*b*c*", "xxaxxbxxcxx", true),
+            ("*a*b*c*", "xxaxxcxxbxx", false),
+        ];
+        for (pattern, s, expected) in cases {
+            assert_eq!(glob_match(pattern, s), expected, "{} {}", pattern, s);
+        }
+        // the backtracking of many stars is linear
+        let s = "a".repeat(100);
+        assert!(!glob_match(&format!("{}b", "a*".repeat(50)), &s));
+    }
+
+    #[test]
+    fn raw_without_filter() {
+        let mut state = MonitorState::new(MonitorFilter::default());
+        assert_eq!(state.accept_raw("OK".to_string()).as_deref(), Some("OK"));
+        let mut state = MonitorState::new(MonitorFilter {
+            db: Some(1),
+            ..Default::default()
+        });
+        assert!(state.accept_raw("OK".to_string()).is_none());
+    }
+
+    #[test]
+    fn no_stats() {
+        let line = r#"1700000000.000000 [0 127.0.0.1:6379] "get" "k""#;
+        for (interval, total) in [(None, 0), (Some(0), 0), (Some(5), 1)] {
+            let mut state = MonitorState::new(MonitorFilter {
+                stats_interval: interval,
+                ..Default::default()
+            });
+            assert!(state.accept(MonitorLine::parse(line).unwrap()).is_some());
+            assert_eq!(state.total, total);
+            assert_eq!(state.keys.len() as u64, total);
+        }
+    }
+}
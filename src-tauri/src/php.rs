@@ -0,0 +1,462 @@
+use serde_json::{Map, Number, Value};
+
+use crate::err::CusError;
+
+// the fields of the json object standing for the php values without a json type
+// the real keys starting with __ get one more _, so they never collide with these
+const CLASS: &str = "__class";
+// the data of a class implementing Serializable, C:3:"Foo":5:{data}
+const SERIALIZED: &str = "__serialized";
+// an enum case, E:7:"Foo:Bar";
+const ENUM: &str = "__enum";
+// the value numbered n again, r:n; such as an object containing itself
+const REF: &str = "__ref";
+// a php reference to the value numbered n, R:n; such as $a = [&$x, &$x]
+const VAR_REF: &str = "__var_ref";
+// the arrays and objects nested deeper are rejected, the recursion is bounded
+const MAX_DEPTH: usize = 128;
+
+// the object properties are named like print_r, name:protected and name:Foo:private
+const PROTECTED: &str = ":protected";
+const PRIVATE: &str = ":private";
+
+fn invalid(pos: usize) -> CusError {
+    CusError::App(format!("Invalid php serialized data at {}", pos))
+}
+
+// the php unserialize(), the arrays of the keys 0..n are json arrays
+// the references are kept as the numbers, so they are written back unchanged
+pub fn unserialize(data: &[u8]) -> Result<Value, CusError> {
+    let mut parser = Parser {
+        data,
+        pos: 0,
+        slots: 0,
+        depth: 0,
+    };
+    let value = parser.value()?;
+    if parser.pos != data.len() {
+        return Err(invalid(parser.pos));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    // the count of the values numbered by php for r:n; and R:n;, starting at 1
+    slots: usize,
+    // the arrays and objects being unserialized
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Result<u8, CusError> {
+        let c = *self.data.get(self.pos).ok_or_else(|| invalid(self.pos))?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), CusError> {
+        if self.next()? != c {
+            return Err(invalid(self.pos - 1));
+        }
+        Ok(())
+    }
+
+    // the bytes before the end, the end is consumed
+    fn until(&mut self, end: u8) -> Result<&'a [u8], CusError> {
+        let start = self.pos;
+        let len = self.data[start..]
+            .iter()
+            .position(|c| *c == end)
+            .ok_or_else(|| invalid(start))?;
+        self.pos += len + 1;
+        Ok(&self.data[start..start + len])
+    }
+
+    fn int<T: std::str::FromStr>(&mut self, end: u8) -> Result<T, CusError> {
+        let start = self.pos;
+        std::str::from_utf8(self.until(end)?)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| invalid(start))
+    }
+
+    // <len>:"<bytes>" the length is in bytes
+    fn bytes(&mut self) -> Result<&'a [u8], CusError> {
+        let len: usize = self.int(b':')?;
+        self.expect(b'"')?;
+        let start = self.pos;
+        let end = start.checked_add(len).ok_or_else(|| invalid(start))?;
+        let s = self.data.get(start..end).ok_or_else(|| invalid(start))?;
+        self.pos = end;
+        self.expect(b'"')?;
+        Ok(s)
+    }
+
+    // the json string can not hold the other bytes losslessly, they are rejected
+    fn text(&self, bytes: &[u8], start: usize) -> Result<String, CusError> {
+        std::str::from_utf8(bytes)
+            .map(String::from)
+            .map_err(|_| invalid(start))
+    }
+
+    fn string(&mut self) -> Result<String, CusError> {
+        let start = self.pos;
+        let bytes = self.bytes()?;
+        self.text(bytes, start)
+    }
+
+    // the items of the array or the object, the depth is limited
+    fn nested(&mut self, start: usize) -> Result<(), CusError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(invalid(start));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    // the key of an array or the name of a property, not numbered
+    fn key(&mut self) -> Result<(String, bool), CusError> {
+        let tag = self.next()?;
+        self.expect(b':')?;
+        match tag {
+            b'i' => Ok((self.int::<i64>(b';')?.to_string(), true)),
+            b's' => {
+                let s = self.string()?;
+                self.expect(b';')?;
+                Ok((s, false))
+            }
+            _ => Err(invalid(self.pos - 2)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, CusError> {
+        let start = self.pos;
+        let tag = self.next()?;
+        // R:n; is the only value php does not number
+        if tag != b'R' {
+            self.slots += 1;
+        }
+        if tag == b'N' {
+            self.expect(b';')?;
+            return Ok(Value::Null);
+        }
+        self.expect(b':')?;
+        let value = match tag {
+            b'b' => Value::Bool(self.int::<u8>(b';')? != 0),
+            b'i' => Value::from(self.int::<i64>(b';')?),
+            b'd' => {
+                let s = self.until(b';')?;
+                let s = self.text(s, start)?;
+                match s.parse::<f64>().ok().and_then(Number::from_f64) {
+                    Some(n) => Value::Number(n),
+                    // INF, -INF and NAN
+                    None => Value::String(s),
+                }
+            }
+            b's' => {
+                let s = self.string()?;
+                self.expect(b';')?;
+                Value::String(s)
+            }
+            b'a' => {
+                let count: usize = self.int(b':')?;
+                self.expect(b'{')?;
+                self.nested(start)?;
+                let mut items = vec![];
+                for _ in 0..count {
+                    let key = self.key()?;
+                    items.push((key, self.value()?));
+                }
+                self.expect(b'}')?;
+                self.depth -= 1;
+                array(items)
+            }
+            b'O' => {
+                let class = self.string()?;
+                self.expect(b':')?;
+                let count: usize = self.int(b':')?;
+                self.expect(b'{')?;
+                self.nested(start)?;
+                let mut m = Map::new();
+                m.insert(CLASS.to_string(), Value::String(class));
+                for _ in 0..count {
+                    let (name, _) = self.key()?;
+                    let value = self.value()?;
+                    m.insert(escape_key(property_name(&name)), value);
+                }
+                self.expect(b'}')?;
+                self.depth -= 1;
+                Value::Object(m)
+            }
+            b'C' => {
+                let class = self.string()?;
+                self.expect(b':')?;
+                let len: usize = self.int(b':')?;
+                self.expect(b'{')?;
+                let start = self.pos;
+                let end = start.checked_add(len).ok_or_else(|| invalid(start))?;
+                let data = self.data.get(start..end).ok_or_else(|| invalid(start))?;
+                self.pos = end;
+                self.expect(b'}')?;
+                let mut m = Map::new();
+                m.insert(CLASS.to_string(), Value::String(class));
+                m.insert(
+                    SERIALIZED.to_string(),
+                    Value::String(self.text(data, start)?),
+                );
+                Value::Object(m)
+            }
+            b'E' => {
+                let name = self.string()?;
+                self.expect(b';')?;
+                let mut m = Map::new();
+                m.insert(ENUM.to_string(), Value::String(name));
+                Value::Object(m)
+            }
+            b'r' | b'R' => {
+                let n: usize = self.int(b';')?;
+                // the r:n; itself is numbered already
+                let numbered = if tag == b'r' {
+                    self.slots - 1
+                } else {
+                    self.slots
+                };
+                if n == 0 || n > numbered {
+                    return Err(invalid(start));
+                }
+                let name = if tag == b'r' { REF } else { VAR_REF };
+                let mut m = Map::new();
+                m.insert(name.to_string(), Value::from(n));
+                Value::Object(m)
+            }
+            _ => return Err(invalid(start)),
+        };
+        Ok(value)
+    }
+}
+
+fn array(items: Vec<((String, bool), Value)>) -> Value {
+    let is_list = items
+        .iter()
+        .enumerate()
+        .all(|(i, ((k, is_int), _))| *is_int && *k == i.to_string());
+    if is_list {
+        Value::Array(items.into_iter().map(|(_, v)| v).collect())
+    } else {
+        Value::Object(
+            items
+                .into_iter()
+                .map(|((k, _), v)| (escape_key(k), v))
+                .collect(),
+        )
+    }
+}
+
+fn escape_key(k: String) -> String {
+    if k.starts_with("__") {
+        format!("_{}", k)
+    } else {
+        k
+    }
+}
+
+fn unescape_key(k: &str) -> &str {
+    if k.starts_with("___") {
+        &k[1..]
+    } else {
+        k
+    }
+}
+
+// \0*\0name is protected, \0Foo\0name is private to Foo
+fn property_name(name: &str) -> String {
+    match name.strip_prefix('\0').and_then(|s| s.split_once('\0')) {
+        Some(("*", name)) => format!("{}{}", name, PROTECTED),
+        Some((class, name)) => format!("{}:{}{}", name, class, PRIVATE),
+        None => name.to_string(),
+    }
+}
+
+fn property_key(name: &str) -> String {
+    if let Some(name) = name.strip_suffix(PROTECTED) {
+        return format!("\0*\0{}", name);
+    }
+    if let Some((name, class)) = name.strip_suffix(PRIVATE).and_then(|s| s.rsplit_once(':')) {
+        return format!("\0{}\0{}", class, name);
+    }
+    name.to_string()
+}
+
+// the php serialize() of the json from unserialize
+pub fn serialize(value: &Value) -> String {
+    let mut s = String::new();
+    write(value, &mut s);
+    s
+}
+
+fn write_str(v: &str, s: &mut String) {
+    s.push_str(&format!("s:{}:\"{}\";", v.len(), v));
+}
+
+// the integer strings are the integer keys, as php converts them
+fn write_key(k: &str, s: &mut String) {
+    match k.parse::<i64>() {
+        Ok(i) if i.to_string() == k => s.push_str(&format!("i:{};", i)),
+        _ => write_str(k, s),
+    }
+}
+
+fn write(value: &Value, s: &mut String) {
+    match value {
+        Value::Null => s.push_str("N;"),
+        Value::Bool(b) => s.push_str(&format!("b:{};", *b as u8)),
+        // the integers out of the php range are floats in php, the digits are kept
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => s.push_str(&format!("i:{};", i)),
+            (None, Some(u)) => s.push_str(&format!("d:{};", u)),
+            _ => s.push_str(&format!("d:{};", n.as_f64().unwrap_or_default())),
+        },
+        Value::String(v) => write_str(v, s),
+        Value::Array(items) => {
+            s.push_str(&format!("a:{}:{{", items.len()));
+            for (i, v) in items.iter().enumerate() {
+                s.push_str(&format!("i:{};", i));
+                write(v, s);
+            }
+            s.push('}');
+        }
+        Value::Object(m) => {
+            let text = |k: &str| m.get(k).and_then(|v| v.as_str());
+            if let Some(class) = text(CLASS) {
+                if let Some(data) = text(SERIALIZED) {
+                    s.push_str(&format!(
+                        "C:{}:\"{}\":{}:{{{}}}",
+                        class.len(),
+                        class,
+                        data.len(),
+                        data
+                    ));
+                    return;
+                }
+                s.push_str(&format!(
+                    "O:{}:\"{}\":{}:{{",
+                    class.len(),
+                    class,
+                    m.len() - 1
+                ));
+                for (k, v) in m.iter().filter(|(k, _)| *k != CLASS) {
+                    write_str(&property_key(unescape_key(k)), s);
+                    write(v, s);
+                }
+                s.push('}');
+                return;
+            }
+            if m.len() == 1 {
+                if let Some(name) = text(ENUM) {
+                    s.push_str(&format!("E:{}:\"{}\";", name.len(), name));
+                    return;
+                }
+                // the values are numbered the same as unserialized if none is added or removed
+                if let Some(n) = m.get(REF).and_then(|v| v.as_u64()) {
+                    s.push_str(&format!("r:{};", n));
+                    return;
+                }
+                if let Some(n) = m.get(VAR_REF).and_then(|v| v.as_u64()) {
+                    s.push_str(&format!("R:{};", n));
+                    return;
+                }
+            }
+            s.push_str(&format!("a:{}:{{", m.len()));
+            for (k, v) in m {
+                write_key(unescape_key(k), s);
+                write(v, s);
+            }
+            s.push('}');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(data: &str) {
+        let value = unserialize(data.as_bytes()).unwrap();
+        assert_eq!(serialize(&value), data, "{}", value);
+    }
+
+    #[test]
+    fn values() {
+        for data in [
+            "N;",
+            "b:1;",
+            "i:-7;",
+            "d:0.5;",
+            "s:6:\"héllo\";",
+            "a:2:{i:0;s:1:\"a\";i:1;i:2;}",
+            "a:1:{s:1:\"k\";a:0:{}}",
+            "O:3:\"Foo\":1:{s:3:\"bar\";i:1;}",
+            "C:3:\"Foo\":3:{abc}",
+            "E:7:\"Foo:Bar\";",
+        ] {
+            round_trip(data);
+        }
+    }
+
+    #[test]
+    fn references() {
+        // an object containing itself
+        let data = "O:8:\"stdClass\":1:{s:4:\"self\";r:1;}";
+        let value = unserialize(data.as_bytes()).unwrap();
+        assert_eq!(value["self"], json!({ "__ref": 1 }));
+        round_trip(data);
+        // $x = 1; [&$x, &$x, $o, $o]
+        let data = "a:4:{i:0;i:1;i:1;R:2;i:2;O:8:\"stdClass\":0:{}i:3;r:3;}";
+        let value = unserialize(data.as_bytes()).unwrap();
+        assert_eq!(value[1], json!({ "__var_ref": 2 }));
+        assert_eq!(value[3], json!({ "__ref": 3 }));
+        round_trip(data);
+        // the nested references are kept, not copied
+        round_trip("a:2:{i:0;a:1:{i:0;r:1;}i:1;a:1:{i:0;R:3;}}");
+        // the numbers not seen yet
+        assert!(unserialize(b"a:1:{i:0;r:2;}").is_err());
+        assert!(unserialize(b"a:1:{i:0;R:0;}").is_err());
+    }
+
+    #[test]
+    fn depth() {
+        let nested = |n: usize| "a:1:{i:0;".repeat(n) + "N;" + &"}".repeat(n);
+        assert!(unserialize(nested(MAX_DEPTH).as_bytes()).is_ok());
+        assert!(unserialize(nested(MAX_DEPTH + 1).as_bytes()).is_err());
+        assert!(unserialize(nested(100_000).as_bytes()).is_err());
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        assert!(unserialize(b"s:2:\"\xff\xfe\";").is_err());
+        assert!(unserialize(b"C:3:\"Foo\":1:{\xff}").is_err());
+        assert!(unserialize(b"d:\xff;").is_err());
+        // the length splitting a character
+        assert!(unserialize("s:1:\"é\";".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn marker_keys() {
+        let data = "a:1:{s:7:\"__class\";s:1:\"x\";}";
+        let value = unserialize(data.as_bytes()).unwrap();
+        assert_eq!(value, json!({ "___class": "x" }));
+        round_trip(data);
+        round_trip("a:2:{s:5:\"__ref\";i:1;s:3:\"___\";N;}");
+        round_trip("O:3:\"Foo\":1:{s:7:\"__class\";s:1:\"x\";}");
+        round_trip("a:1:{s:6:\"__enum\";s:1:\"x\";}");
+    }
+
+    #[test]
+    fn large_integer() {
+        assert_eq!(serialize(&json!(u64::MAX)), format!("d:{};", u64::MAX));
+        assert_eq!(serialize(&json!(i64::MIN)), format!("i:{};", i64::MIN));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::err::CusError;
+use crate::ssh::SshProxy;
+use base64::Engine;
+use ssh_jumper::model::SshForwarderEnd;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ProxyKind {
+    Socks5,
+    Http,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProxyParams {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub target_host: String,
+    pub target_port: u16,
+    // the connect timeout of the connection, including the handshake
+    pub timeout: Option<Duration>,
+}
+
+// create the local forwarder through the proxy
+pub async fn create_tunnel<T: SshProxy>(t: &mut T) -> Result<Option<()>, CusError> {
+    if let Some(config) = t.get_proxy_config() {
+        // check the proxy is available before listening
+        connect(&config).await?;
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let (mut tx, rx) = oneshot::channel::<SshForwarderEnd>();
+        tokio::spawn(async move {
+            loop {
+                let accepted = tokio::select! {
+                    _ = tx.closed() => return,
+                    r = listener.accept() => r,
+                };
+                match accepted {
+                    Ok((mut local, _)) => {
+                        let config = config.clone();
+                        tokio::spawn(async move {
+                            if let Ok(mut remote) = connect(&config).await {
+                                let _ =
+                                    tokio::io::copy_bidirectional(&mut local, &mut remote).await;
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        let _ = tx.send(SshForwarderEnd::LocalConnectFail(e));
+                        return;
+                    }
+                }
+            }
+        });
+        t.store_addr(addr, rx);
+    }
+    Ok(None)
+}
+
+// connect to the target through the proxy
+async fn connect(config: &ProxyParams) -> Result<TcpStream, CusError> {
+    match config.timeout {
+        Some(d) => tokio::time::timeout(d, handshake(config))
+            .await
+            .map_err(|_| CusError::connect_timeout(d))?,
+        None => handshake(config).await,
+    }
+}
+
+async fn handshake(config: &ProxyParams) -> Result<TcpStream, CusError> {
+    let mut stream = TcpStream::connect((config.host.as_str(), config.port)).await?;
+    match config.kind {
+        ProxyKind::Socks5 => socks5_handshake(&mut stream, config).await?,
+        ProxyKind::Http => http_handshake(&mut stream, config).await?,
+    }
+    Ok(stream)
+}
+
+fn proxy_err(config: &ProxyParams, msg: &str) -> CusError {
+    CusError::App(format!(
+        "Proxy {}:{} error: {}",
+        config.host, config.port, msg
+    ))
+}
+
+// the length prefix of socks5 is a byte
+fn socks5_len(s: &str, name: &str) -> Result<u8, CusError> {
+    u8::try_from(s.len())
+        .map_err(|_| CusError::build(&format!("The socks5 {} is longer than 255 bytes", name)))
+}
+
+// https://datatracker.ietf.org/doc/html/rfc1928
+async fn socks5_handshake(stream: &mut TcpStream, config: &ProxyParams) -> Result<(), CusError> {
+    let auth = match (&config.username, &config.password) {
+        (Some(u), Some(p)) if !u.is_empty() => Some((u, p)),
+        _ => None,
+    };
+    if auth.is_some() {
+        stream.write_all(&[5, 2, 0, 2]).await?;
+    } else {
+        stream.write_all(&[5, 1, 0]).await?;
+    }
+    let mut buf = [0u8; 2];
+    stream.read_exact(&mut buf).await?;
+    if buf[0] != 5 {
+        return Err(proxy_err(config, "not a socks5 proxy"));
+    }
+    match (buf[1], auth) {
+        (0, _) => {}
+        // https://datatracker.ietf.org/doc/html/rfc1929
+        (2, Some((username, password))) => {
+            let mut req = vec![1, socks5_len(username, "username")?];
+            req.extend_from_slice(username.as_bytes());
+            req.push(socks5_len(password, "password")?);
+            req.extend_from_slice(password.as_bytes());
+            stream.write_all(&req).await?;
+            stream.read_exact(&mut buf).await?;
+            if buf[1] != 0 {
+                return Err(proxy_err(config, "authentication failed"));
+            }
+        }
+        _ => return Err(proxy_err(config, "no acceptable authentication method")),
+    }
+
+    let mut req = vec![5, 1, 0];
+    match config.target_host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            req.push(1);
+            req.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            req.push(4);
+            req.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            req.push(3);
+            req.push(socks5_len(&config.target_host, "host")?);
+            req.extend_from_slice(config.target_host.as_bytes());
+        }
+    }
+    req.extend_from_slice(&config.target_port.to_be_bytes());
+    stream.write_all(&req).await?;
+
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    if head[1] != 0 {
+        return Err(proxy_err(
+            config,
+            &format!("connect to target failed, reply code {}", head[1]),
+        ));
+    }
+    // skip the bound address and port
+    let len = match head[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        _ => return Err(proxy_err(config, "invalid reply")),
+    };
+    let mut bound = vec![0u8; len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+async fn http_handshake(stream: &mut TcpStream, config: &ProxyParams) -> Result<(), CusError> {
+    let target = match config.target_host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("[{}]:{}", ip, config.target_port),
+        _ => format!("{}:{}", config.target_host, config.target_port),
+    };
+    let mut req = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", target, target);
+    if let Some(username) = config.username.as_ref().filter(|u| !u.is_empty()) {
+        let credential = format!(
+            "{}:{}",
+            username,
+            config.password.clone().unwrap_or_default()
+        );
+        req.push_str(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            base64::engine::general_purpose::STANDARD.encode(credential)
+        ));
+    }
+    req.push_str("\r\n");
+    stream.write_all(req.as_bytes()).await?;
+
+    // read byte by byte, the data after the header belongs to redis
+    let mut header = vec![];
+    while !header.ends_with(b"\r\n\r\n") {
+        if header.len() > 8192 {
+            return Err(proxy_err(config, "response header too large"));
+        }
+        header.push(stream.read_u8().await?);
+    }
+    let header = String::from_utf8_lossy(&header);
+    let status_line = header.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(()),
+        _ => Err(proxy_err(config, status_line)),
+    }
+}
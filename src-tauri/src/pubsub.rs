@@ -1,9 +1,45 @@
 use chrono::prelude::*;
 use std::collections::HashMap;
 use std::sync::Mutex as SMutex;
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
 
+use crate::err::CusError;
 use crate::response;
+use crate::utils;
+use base64::Engine;
+use serde::Deserialize;
+
+// how the binary payload is sent to the frontend
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadEncoding {
+    // the invalid bytes are escaped as \xff
+    #[default]
+    Utf8,
+    Hex,
+    Base64,
+}
+
+impl PayloadEncoding {
+    pub fn encode(&self, v: Vec<u8>) -> String {
+        match self {
+            Self::Utf8 => match String::from_utf8(v) {
+                Ok(s) => s,
+                Err(e) => utils::binary_to_redis_str(&e.into_bytes()),
+            },
+            Self::Hex => hex::encode(v),
+            Self::Base64 => base64::engine::general_purpose::STANDARD.encode(v),
+        }
+    }
+}
+
+// add or remove channels of a live subscription
+// cmd is subscribe/unsubscribe/psubscribe/punsubscribe/ssubscribe/sunsubscribe
+pub struct PubsubChange {
+    pub cmd: String,
+    pub channels: Vec<String>,
+    pub reply: oneshot::Sender<Result<(), CusError>>,
+}
 
 pub struct PubsubItem {
     pub tx: oneshot::Sender<()>,
@@ -12,6 +48,8 @@ pub struct PubsubItem {
     pub host: String,
     pub id: String,
     pub proxy: Option<String>,
+    // the channels can be changed when set
+    pub control: Option<mpsc::UnboundedSender<PubsubChange>>,
 }
 impl PubsubItem {
     pub fn new(
@@ -28,8 +66,14 @@ impl PubsubItem {
             host,
             id,
             proxy,
+            control: None,
         }
     }
+
+    pub fn with_control(mut self, control: mpsc::UnboundedSender<PubsubChange>) -> Self {
+        self.control = Some(control);
+        self
+    }
 }
 
 // a state to manager pubsub/monitor
@@ -48,6 +92,32 @@ impl PubsubManager {
         }
     }
 
+    // send the change to the subscription and wait for the result
+    pub async fn change(
+        &self,
+        name: &str,
+        cmd: String,
+        channels: Vec<String>,
+    ) -> Result<(), CusError> {
+        let control = self
+            .0
+            .lock()
+            .unwrap()
+            .get(name)
+            .and_then(|x| x.control.clone())
+            .ok_or_else(|| CusError::build("The subscription can not be changed"))?;
+        let (reply, rx) = oneshot::channel();
+        control
+            .send(PubsubChange {
+                cmd,
+                channels,
+                reply,
+            })
+            .map_err(|_| CusError::build("The subscription is closed"))?;
+        rx.await
+            .map_err(|_| CusError::build("The subscription is closed"))?
+    }
+
     pub fn get_conns(&self) -> Vec<response::Conn> {
         let mut vec = vec![];
         for (_, v) in self.0.lock().unwrap().iter() {
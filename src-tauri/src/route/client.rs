@@ -1,9 +1,16 @@
 use crate::{
     connection::{CValue, Manager},
     err::CusError,
+    pubsub::{PubsubItem, PubsubManager},
     request,
+    response::EventResp,
+    utils,
 };
-use redis::{self};
+use redis::{self, FromRedisValue, PushKind};
+use serde::Deserialize;
+use tauri::Emitter;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::oneshot;
 
 pub async fn list(
     _payload: String,
@@ -33,3 +40,78 @@ pub async fn kill(
         _ => Ok(count),
     }
 }
+
+#[derive(Deserialize)]
+struct TrackingArgs {
+    #[serde(default)]
+    bcast: bool,
+    #[serde(default)]
+    prefixes: Vec<String>,
+}
+
+// enable client tracking on the shared RESP3 connection
+// the invalidated keys are emitted to the returned event, none means all keys are invalidated
+// tracking is turned off when the event is cancelled
+pub async fn tracking(
+    window: tauri::Window,
+    pubsub_manager: tauri::State<'_, PubsubManager>,
+    payload: String,
+    cid: u32,
+    manager: tauri::State<'_, Manager>,
+) -> Result<String, CusError> {
+    let args: TrackingArgs = serde_json::from_str(&payload)?;
+    let conn = manager.get(cid).await?;
+    let mut push_rx = conn.subscribe_push()?;
+    let mut cmd = redis::cmd("CLIENT");
+    cmd.arg("TRACKING").arg("ON");
+    if args.bcast {
+        cmd.arg("BCAST");
+        for prefix in &args.prefixes {
+            cmd.arg("PREFIX").arg(prefix);
+        }
+    }
+    manager.execute_with::<String>(&mut cmd, &conn).await?;
+
+    let event_name = utils::random_str(32);
+    let event_name_resp = event_name.clone();
+    let (tx, rx) = oneshot::channel::<()>();
+    pubsub_manager.add(
+        event_name.clone(),
+        PubsubItem::new(
+            tx,
+            event_name.clone(),
+            conn.get_host(),
+            "tracking".to_string(),
+            conn.model.get_proxy(),
+        ),
+    );
+    tokio::spawn(async move {
+        let event_str = event_name.as_str();
+        tokio::select! {
+            _ = async {
+                loop {
+                    let info = match push_rx.recv().await {
+                        Ok(info) => info,
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    };
+                    if info.kind != PushKind::Invalidate {
+                        continue;
+                    }
+                    let keys = match info.data.first() {
+                        Some(v) => Option::<Vec<String>>::from_redis_value(v)?,
+                        None => None,
+                    };
+                    let r = EventResp::new(keys, String::from(event_str));
+                    let _ = window.emit(event_str, serde_json::to_string(&r)?);
+                }
+                Ok::<(), CusError>(())
+            } => {},
+            _ = rx => {}
+        }
+        let mut off = redis::cmd("CLIENT");
+        off.arg("TRACKING").arg("OFF");
+        let _ = conn.execute::<String>(&off, None).await;
+    });
+    Ok(event_name_resp)
+}
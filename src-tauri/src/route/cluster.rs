@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use crate::{
-    connection::{ConnectionWrapper, Manager, Node},
+    connection::{self, Manager, Node, Shard, SlotCoverage},
     err::CusError,
     request::{self, IdArgs},
     response::{KeyWithMemory, ScanLikeResult},
@@ -22,7 +22,7 @@ pub async fn scan(
             if let Some(cursor) = x.get("cursor") {
                 for node in nodes.clone() {
                     if &node.id == node_id {
-                        let mut conn = ConnectionWrapper::build(node).await?;
+                        let conn = manager.connect_node(cid, node).await?;
                         let mut cmd = redis::cmd("scan");
                         cmd.arg(cursor)
                             .arg(&["count", args.count.to_string().as_str()]);
@@ -40,7 +40,7 @@ pub async fn scan(
                         if let Some(types) = &args.types {
                             cmd.arg(&["TYPE", types]);
                         }
-                        let value: Vec<Value> = manager.execute_with(&mut cmd, &mut conn).await?;
+                        let value: Vec<Value> = manager.execute_with(&mut cmd, &conn).await?;
                         let mut result = ScanLikeResult::<String, String>::build(value)?;
                         let mut node_cursor: HashMap<String, String> = HashMap::new();
                         node_cursor.insert(String::from("cursor"), result.cursor);
@@ -64,9 +64,9 @@ pub async fn node_size(
     let nodes: Vec<Node> = manager.get_nodes(cid).await?;
     for n in nodes {
         if n.id == args.id {
-            let mut conn = ConnectionWrapper::build(n).await?;
+            let conn = manager.connect_node(cid, n).await?;
             let value = manager
-                .execute_with(&mut redis::cmd("dbsize"), &mut conn)
+                .execute_with(&mut redis::cmd("dbsize"), &conn)
                 .await?;
             return Ok(i64::from_redis_value(&value)?);
         }
@@ -78,6 +78,21 @@ pub async fn node(cid: u32, manager: tauri::State<'_, Manager>) -> Result<Vec<No
     manager.get_nodes(cid).await
 }
 
+// masters with their replicas
+pub async fn shards(cid: u32, manager: tauri::State<'_, Manager>) -> Result<Vec<Shard>, CusError> {
+    let nodes = manager.get_nodes(cid).await?;
+    Ok(connection::build_shards(&nodes))
+}
+
+// report the slots not served by any master
+pub async fn coverage(
+    cid: u32,
+    manager: tauri::State<'_, Manager>,
+) -> Result<SlotCoverage, CusError> {
+    let nodes = manager.get_nodes(cid).await?;
+    Ok(connection::check_coverage(&nodes))
+}
+
 pub async fn analysis(
     cid: u32,
     payload: String,
@@ -94,7 +109,7 @@ pub async fn analysis(
             if let Some(cursor) = x.get("cursor") {
                 for node in nodes.clone() {
                     if node_id == &node.id {
-                        let mut conn = ConnectionWrapper::build(node).await?;
+                        let conn = manager.connect_node(cid, node).await?;
                         let mut cmd = redis::cmd("scan");
                         cmd.arg(cursor)
                             .arg(&["count", args.count.to_string().as_str()]);
@@ -104,7 +119,7 @@ pub async fn analysis(
                         if let Some(types) = &args.types {
                             cmd.arg(&["TYPE", types]);
                         }
-                        let value: Vec<Value> = manager.execute_with(&mut cmd, &mut conn).await?;
+                        let value: Vec<Value> = manager.execute_with(&mut cmd, &conn).await?;
                         let result = ScanLikeResult::<String, String>::build(value)?;
                         let mut node_cursor: HashMap<String, String> = HashMap::new();
                         node_cursor.insert(String::from("cursor"), result.cursor);
@@ -117,11 +132,11 @@ pub async fn analysis(
                                         .arg("usage")
                                         .arg(&k)
                                         .arg(&["SAMPLES", "0"]),
-                                    &mut conn,
+                                    &conn,
                                 )
                                 .await?;
                             let types: String = manager
-                                .execute_with(redis::cmd("type").arg(&k), &mut conn)
+                                .execute_with(redis::cmd("type").arg(&k), &conn)
                                 .await?;
                             let km = KeyWithMemory {
                                 name: k,
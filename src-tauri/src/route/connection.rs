@@ -2,18 +2,65 @@ use crate::{
     connection::{ConnectionWrapper, Manager},
     err::CusError,
     request::IdArgs,
-    sqlite::Connection,
+    sqlite::{Connection, ImportFormat},
 };
+use serde::Deserialize;
 use tauri::State;
 
+#[derive(Deserialize)]
+struct FilterArgs {
+    group: Option<String>,
+    tag: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SortArgs {
+    ids: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+struct UriArgs {
+    uri: String,
+}
+
+#[derive(Deserialize)]
+struct ToUriArgs {
+    id: u32,
+    #[serde(default)]
+    with_secret: bool,
+}
+
+#[derive(Deserialize)]
+struct ImportArgs {
+    format: ImportFormat,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct ExportArgs {
+    #[serde(default)]
+    omit_secrets: bool,
+}
+
 pub fn add(payload: String) -> Result<Connection, CusError> {
     let mut connection: Connection = serde_json::from_str(&payload)?;
     connection.save()?;
     Ok(connection)
 }
 
-pub async fn get() -> Result<Vec<Connection>, CusError> {
-    Connection::all()
+pub async fn get(payload: String) -> Result<Vec<Connection>, CusError> {
+    let args: FilterArgs = serde_json::from_str(&payload)?;
+    Connection::filter(args.group.as_deref(), args.tag.as_deref())
+}
+
+pub fn groups() -> Result<Vec<String>, CusError> {
+    Connection::groups()
+}
+
+// save the order of the connections
+pub fn sort(payload: String) -> Result<(), CusError> {
+    let args: SortArgs = serde_json::from_str(&payload)?;
+    Connection::reorder(&args.ids)
 }
 
 pub fn del(payload: String) -> Result<(), CusError> {
@@ -39,3 +86,33 @@ pub async fn close(cid: u32, manager: State<'_, Manager>) -> Result<(), CusError
     manager.remove(cid).await;
     Ok(())
 }
+
+// parse the uri to a connection, the connection is not saved
+pub fn parse_uri(payload: String) -> Result<Connection, CusError> {
+    let args: UriArgs = serde_json::from_str(&payload)?;
+    Connection::from_uri(&args.uri)
+}
+
+pub fn uri(payload: String) -> Result<String, CusError> {
+    let args: ToUriArgs = serde_json::from_str(&payload)?;
+    let conn = Connection::first(args.id)?;
+    Ok(conn.to_uri(args.with_secret))
+}
+
+pub fn import(payload: String) -> Result<Vec<Connection>, CusError> {
+    let args: ImportArgs = serde_json::from_str(&payload)?;
+    let mut connections = Connection::parse_import(args.format, &args.content)?;
+    for connection in connections.iter_mut() {
+        connection.save()?;
+    }
+    Ok(connections)
+}
+
+pub fn export(payload: String) -> Result<Vec<Connection>, CusError> {
+    let args: ExportArgs = serde_json::from_str(&payload)?;
+    let connections = Connection::all()?;
+    if args.omit_secrets {
+        return Ok(connections.iter().map(|c| c.without_secrets()).collect());
+    }
+    Ok(connections)
+}
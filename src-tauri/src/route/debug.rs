@@ -1,26 +1,140 @@
-use tauri::Emitter;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{Emitter, WindowEvent};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::oneshot;
 
-use crate::{connection::Manager, err::CusError, model::Command, pubsub::PubsubManager, response};
+use crate::request::NameArgs;
+use crate::sqlite::{CommandLog, CommandLogFilter};
+use crate::{connection::Manager, err::CusError, pubsub::PubsubManager, response, utils};
 
+// the log windows listening, closed by the event name
+static SUBSCRIBERS: Lazy<Mutex<HashMap<String, oneshot::Sender<()>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// every log window receives the commands with its own event
 pub async fn log(
     manager: tauri::State<'_, Manager>,
     window: tauri::Window,
-) -> Result<(), CusError> {
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<Command>(32);
-    manager.set_tx(tx).await;
+) -> Result<String, CusError> {
+    let mut rx = manager.subscribe_debug();
+    let event_name = utils::random_str(32);
+    let (tx, mut cancel_rx) = oneshot::channel::<()>();
+    SUBSCRIBERS.lock().unwrap().insert(event_name.clone(), tx);
+    // the window closed without cancel, the sender dropped stops the task
+    let name = event_name.clone();
+    window.on_window_event(move |e| {
+        if matches!(e, WindowEvent::Destroyed) {
+            SUBSCRIBERS.lock().unwrap().remove(&name);
+        }
+    });
+    let event_str = event_name.clone();
     tokio::spawn(async move {
-        while let Some(cmd) = rx.recv().await {
-            let _ = window.emit("debug", serde_json::to_string(&cmd).unwrap());
+        loop {
+            tokio::select! {
+                r = rx.recv() => match r {
+                    Ok(cmd) => {
+                        let payload = serde_json::to_string(&cmd).unwrap_or_default();
+                        if window.emit(event_str.as_str(), payload).is_err() {
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut cancel_rx => break,
+            }
         }
+        SUBSCRIBERS.lock().unwrap().remove(&event_str);
     });
-    Ok(())
+    Ok(event_name)
 }
 
-pub async fn cancel(manager: tauri::State<'_, Manager>) -> Result<(), CusError> {
-    manager.remove_tx().await;
+pub async fn cancel(payload: String) -> Result<(), CusError> {
+    let args: NameArgs = serde_json::from_str(&payload)?;
+    SUBSCRIBERS.lock().unwrap().remove(&args.name);
     Ok(())
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct PersistArgs {
+    enabled: bool,
+}
+
+// the commands are saved to the history only if enabled
+pub async fn persist() -> Result<PersistArgs, CusError> {
+    Ok(PersistArgs {
+        enabled: CommandLog::is_persisted(),
+    })
+}
+
+pub async fn set_persist(payload: String) -> Result<(), CusError> {
+    let args: PersistArgs = serde_json::from_str(&payload)?;
+    CommandLog::set_persisted(args.enabled)
+}
+
+// the persisted logs
+pub async fn history(payload: String) -> Result<Vec<CommandLog>, CusError> {
+    let filter: CommandLogFilter = serde_json::from_str(&payload)?;
+    CommandLog::query(&filter)
+}
+
+pub async fn clear() -> Result<(), CusError> {
+    CommandLog::clear()
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize)]
+struct ExportArgs {
+    #[serde(default)]
+    format: ExportFormat,
+    #[serde(flatten)]
+    filter: CommandLogFilter,
+}
+
+// the filtered logs as json or csv text
+pub async fn export(payload: String) -> Result<String, CusError> {
+    let args: ExportArgs = serde_json::from_str(&payload)?;
+    let logs = CommandLog::query(&args.filter)?;
+    match args.format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(&logs)?),
+        ExportFormat::Csv => {
+            let mut s = String::from("id,connection_id,host,command,success,duration,created_at\n");
+            for log in logs {
+                let connection_id = log.connection_id.map(|v| v.to_string()).unwrap_or_default();
+                s.push_str(&format!(
+                    "{},{},{},{},{},{},{}\n",
+                    log.id,
+                    connection_id,
+                    csv_field(&log.host),
+                    csv_field(&log.cmd),
+                    log.success,
+                    log.duration,
+                    csv_field(&log.created_at)
+                ));
+            }
+            Ok(s)
+        }
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
 pub async fn clients(
     manager: tauri::State<'_, Manager>,
     pubsub: tauri::State<'_, PubsubManager>,
@@ -0,0 +1,186 @@
+use crate::connection::{Connectable, Connection, ConnectionWrapper, Manager};
+use crate::err::CusError;
+use crate::pubsub::{PayloadEncoding, PubsubItem, PubsubManager};
+use crate::response::EventResp;
+use crate::route::config;
+use crate::sqlite::Connection as ConnectionModel;
+use crate::utils;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager as _, State};
+use tokio::sync::oneshot;
+
+const NOTIFY_CONFIG: &str = "notify-keyspace-events";
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum WatchMode {
+    // __keyspace@<db>__:<key pattern>, the payload is the event
+    #[default]
+    Keyspace,
+    // __keyevent@<db>__:<event pattern>, the payload is the key
+    Keyevent,
+}
+
+#[derive(Deserialize)]
+struct WatchArgs {
+    #[serde(default)]
+    db: u8,
+    #[serde(default = "default_pattern")]
+    pattern: String,
+    #[serde(default)]
+    mode: WatchMode,
+    // set notify-keyspace-events before watching, such as KEA
+    events: Option<String>,
+}
+
+fn default_pattern() -> String {
+    String::from("*")
+}
+
+#[derive(Serialize, Debug)]
+struct KeyEvent {
+    key: String,
+    // set, del, expired, evicted...
+    event: String,
+    db: u8,
+    host: String,
+}
+
+impl KeyEvent {
+    // parse the message of the keyspace/keyevent channel
+    fn build(channel: &str, payload: String, host: &str) -> Option<Self> {
+        let (prefix, name) = channel.split_once("__:")?;
+        let (kind, db) = prefix.trim_start_matches("__").split_once('@')?;
+        let db = db.parse().ok()?;
+        let (key, event) = match kind {
+            "keyspace" => (name.to_string(), payload),
+            "keyevent" => (payload, name.to_string()),
+            _ => return None,
+        };
+        Some(Self {
+            key,
+            event,
+            db,
+            host: host.to_string(),
+        })
+    }
+}
+
+// watch the key changes by keyspace notifications
+// every master is subscribed in cluster, the notifications are local to the node
+pub async fn watch(
+    window: tauri::Window,
+    pubsub_manager: State<'_, PubsubManager>,
+    manager: State<'_, Manager>,
+    payload: String,
+    cid: u32,
+) -> Result<String, CusError> {
+    let args: WatchArgs = serde_json::from_str(&payload)?;
+    let model = ConnectionModel::first(cid)?;
+    let targets = if model.is_cluster {
+        let nodes: Vec<_> = manager
+            .get_nodes(cid)
+            .await?
+            .into_iter()
+            .filter(|n| n.is_master() && !n.is_failed())
+            .collect();
+        // the config is local to the node, every master is set
+        if let Some(events) = &args.events {
+            for node in &nodes {
+                let conn = ConnectionWrapper::build(node.clone()).await?;
+                manager
+                    .execute_with::<()>(
+                        redis::cmd("CONFIG")
+                            .arg("SET")
+                            .arg(NOTIFY_CONFIG)
+                            .arg(events),
+                        &conn,
+                    )
+                    .await?;
+            }
+        }
+        nodes.iter().map(|n| n.get_params()).collect()
+    } else {
+        if let Some(events) = &args.events {
+            let edit = serde_json::json!({ "name": NOTIFY_CONFIG, "value": events });
+            config::edit(edit.to_string(), cid, manager).await?;
+        }
+        vec![model.get_params()]
+    };
+    // cluster only has db 0
+    let db = if model.is_cluster { 0 } else { args.db };
+    let channel = match args.mode {
+        WatchMode::Keyspace => format!("__keyspace@{}__:{}", db, args.pattern),
+        WatchMode::Keyevent => format!("__keyevent@{}__:{}", db, args.pattern),
+    };
+
+    let mut connections = vec![];
+    let mut streams = vec![];
+    for mut params in targets {
+        // the dedicated connection always uses RESP2
+        params.redis_params.protocol = redis::ProtocolVersion::RESP2;
+        let mut connection = Connection::new(params);
+        connection.open_tunnel().await?;
+        let mut pubsub = connection.get_pubsub().await?;
+        pubsub.psubscribe(&channel).await?;
+        let host = connection.get_host();
+        streams.push(Box::pin(
+            pubsub.into_on_message().map(move |msg| (host.clone(), msg)),
+        ));
+        connections.push(connection);
+    }
+    let first = connections
+        .first()
+        .ok_or_else(|| CusError::build("No node to watch"))?;
+
+    let event_name = utils::random_str(32);
+    let event_name_resp = event_name.clone();
+    let (tx, rx) = oneshot::channel::<()>();
+    pubsub_manager.add(
+        event_name.clone(),
+        PubsubItem::new(
+            tx,
+            event_name.clone(),
+            first.get_host(),
+            "keyspace".to_string(),
+            first.get_proxy(),
+        ),
+    );
+    tokio::spawn(async move {
+        let event_str = event_name.as_str();
+        let mut messages = stream::select_all(streams);
+        let result = tokio::select! {
+            r = async {
+                while let Some((host, msg)) = messages.next().await {
+                    // the keys may not be utf8, they are escaped
+                    let (Ok(channel), Ok(payload)) =
+                        (msg.get_channel::<Vec<u8>>(), msg.get_payload::<Vec<u8>>())
+                    else {
+                        continue;
+                    };
+                    let channel = PayloadEncoding::Utf8.encode(channel);
+                    let payload = PayloadEncoding::Utf8.encode(payload);
+                    if let Some(event) = KeyEvent::build(&channel, payload, &host) {
+                        let r = EventResp::new(event, String::from(event_str));
+                        let _ = window.emit(event_str, serde_json::to_string(&r)?);
+                    }
+                }
+                Ok::<(), CusError>(())
+            } => r,
+            // closed by the user
+            _ = rx => return,
+        };
+        drop(connections);
+        // the stream ends when a node is disconnected, the watch is closed with the reason
+        window.state::<PubsubManager>().close(&event_name);
+        let message = match result {
+            Ok(_) => String::from("The connection is closed"),
+            Err(e) => e.to_string(),
+        };
+        let mut r = EventResp::new(message, event_name.clone());
+        r.success = false;
+        let _ = window.emit(event_str, serde_json::to_string(&r).unwrap_or_default());
+    });
+    Ok(event_name_resp)
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{err::CusError, logger};
+
+#[derive(Serialize, Deserialize)]
+pub struct LevelArgs {
+    level: String,
+}
+
+// the recent backend logs attached to the bug report
+pub async fn recent(payload: String) -> Result<Vec<serde_json::Value>, CusError> {
+    let args: logger::RecentArgs = serde_json::from_str(&payload)?;
+    logger::recent(&args)
+}
+
+pub async fn level() -> Result<LevelArgs, CusError> {
+    Ok(LevelArgs {
+        level: logger::level(),
+    })
+}
+
+pub async fn set_level(payload: String) -> Result<(), CusError> {
+    let args: LevelArgs = serde_json::from_str(&payload)?;
+    logger::set_level(&args.level)
+}
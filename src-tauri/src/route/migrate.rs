@@ -31,26 +31,14 @@ pub async fn migrate(
     let args: MigrateArgs = serde_json::from_str(&payload)?;
     let mut result = vec![];
     let source_model = sqlite::Connection::first(cid)?;
-    let mut source_connection = connection::ConnectionWrapper::build(source_model).await?;
-    if !source_connection.is_cluster() {
-        if let Some(db) = args.source_db {
-            manager
-                .execute_with::<String>(redis::cmd("select").arg(db), &mut source_connection)
-                .await?;
-        } else {
-            return Err(CusError::build("target db not select"));
-        }
+    let source_connection = connection::ConnectionWrapper::build(source_model).await?;
+    if !source_connection.is_cluster() && args.source_db.is_none() {
+        return Err(CusError::build("target db not select"));
     }
     let target_model = sqlite::Connection::first(args.target_id)?;
-    let mut target_connection = connection::ConnectionWrapper::build(target_model).await?;
-    if !target_connection.is_cluster() {
-        if let Some(db) = args.target_db {
-            manager
-                .execute_with::<String>(redis::cmd("select").arg(db), &mut target_connection)
-                .await?;
-        } else {
-            return Err(CusError::build("target db not select"));
-        }
+    let target_connection = connection::ConnectionWrapper::build(target_model).await?;
+    if !target_connection.is_cluster() && args.target_db.is_none() {
+        return Err(CusError::build("target db not select"));
     }
 
     for k in args.keys {
@@ -60,7 +48,11 @@ pub async fn migrate(
             message: String::default(),
         };
         let ttl: i64 = manager
-            .execute_with(redis::cmd("pttl").arg(&k), &mut source_connection)
+            .execute_db(
+                redis::cmd("pttl").arg(&k),
+                &source_connection,
+                args.source_db,
+            )
             .await?;
         let mut restore_cmd = redis::cmd("restore");
         restore_cmd.arg(&k);
@@ -80,14 +72,18 @@ pub async fn migrate(
             }
         }
         let dump_value: Vec<u8> = manager
-            .execute_with(redis::cmd("dump").arg(&k), &mut source_connection)
+            .execute_db(
+                redis::cmd("dump").arg(&k),
+                &source_connection,
+                args.source_db,
+            )
             .await?;
         restore_cmd.arg(&dump_value);
         if args.replace {
             restore_cmd.arg("replace");
         }
         let restore_result: Result<String, CusError> = manager
-            .execute_with(&mut restore_cmd, &mut target_connection)
+            .execute_db(&mut restore_cmd, &target_connection, args.target_db)
             .await;
         match restore_result {
             Ok(s) => {
@@ -95,7 +91,11 @@ pub async fn migrate(
                 r.success = true;
                 if args.delete {
                     let _: Result<i64, CusError> = manager
-                        .execute_with(redis::cmd("del").arg(&k), &mut source_connection)
+                        .execute_db(
+                            redis::cmd("del").arg(&k),
+                            &source_connection,
+                            args.source_db,
+                        )
                         .await;
                 }
             }
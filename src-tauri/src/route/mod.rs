@@ -1,8 +1,11 @@
+use std::time::Instant;
 use tauri::Window;
+use tracing::Instrument;
 
-use crate::connection::{EventManager, Manager};
+use crate::connection::{EventManager, Manager, WRITE_CONFIRMED};
 use crate::err::CusError;
 use crate::pubsub::PubsubManager;
+use crate::redact;
 use crate::response::Response;
 
 pub mod bloom;
@@ -19,12 +22,18 @@ pub mod hash;
 pub mod hyperloglog;
 pub mod json;
 pub mod key;
+pub mod keyspace;
 pub mod list;
+pub mod logs;
 pub mod memory;
 pub mod migrate;
 pub mod pubsub;
+pub mod recording;
+pub mod script;
 pub mod server;
 pub mod set;
+pub mod snippet;
+pub mod ssh;
 pub mod string;
 pub mod tdigest;
 pub mod terminal;
@@ -34,6 +43,7 @@ pub mod transfer;
 pub mod zset;
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn dispatch(
     pubsub: tauri::State<'_, PubsubManager>,
     manager: tauri::State<'_, Manager>,
@@ -42,16 +52,54 @@ pub async fn dispatch(
     path: String,
     cid: u32,
     payload: String,
+    confirmed: Option<bool>,
+) -> Result<String, CusError> {
+    let span = tracing::info_span!("dispatch", path = %path, cid);
+    async move {
+        tracing::debug!(payload = %redact::payload(&payload));
+        let start = Instant::now();
+        // writes to production connection are rejected unless confirmed
+        let r = WRITE_CONFIRMED
+            .scope(
+                confirmed.unwrap_or(false),
+                handle(pubsub, manager, event_manage, window, path, cid, payload),
+            )
+            .await;
+        let duration = start.elapsed().as_micros() as u64;
+        match &r {
+            Ok(_) => tracing::info!(duration, "done"),
+            Err(e) => tracing::warn!(duration, error = %e, "failed"),
+        }
+        r
+    }
+    .instrument(span)
+    .await
+}
+
+async fn handle(
+    pubsub: tauri::State<'_, PubsubManager>,
+    manager: tauri::State<'_, Manager>,
+    event_manage: tauri::State<'_, EventManager>,
+    window: Window,
+    path: String,
+    cid: u32,
+    payload: String,
 ) -> Result<String, CusError> {
-    dbg!(&path);
-    dbg!(&payload);
     let r = match path.as_str() {
-        "connections/get" => Response::string(connection::get().await?),
+        "connections/get" => Response::string(connection::get(payload).await?),
+        "connections/groups" => Response::string(connection::groups()?),
+        "connections/sort" => Response::string(connection::sort(payload)?),
         "connections/add" => Response::string(connection::add(payload)?),
         "connections/del" => Response::string(connection::del(payload)?),
         "connections/update" => Response::string(connection::update(payload)?),
         "connections/open" => Response::string(connection::open(cid, manager).await?),
         "connections/close" => Response::string(connection::close(cid, manager).await?),
+        "connections/parse-uri" => Response::string(connection::parse_uri(payload)?),
+        "connections/uri" => Response::string(connection::uri(payload)?),
+        "connections/import" => Response::string(connection::import(payload)?),
+        "connections/export" => Response::string(connection::export(payload)?),
+        "ssh/pending" => Response::string(ssh::pending()?),
+        "ssh/trust" => Response::string(ssh::trust(payload)?),
         "server/ping" => Response::string(server::ping(payload, manager).await?),
         "server/info" => Response::string(server::info(cid, manager).await?),
         "server/version" => Response::string(server::version(cid, manager).await?),
@@ -191,23 +239,40 @@ pub async fn dispatch(
         "db/flush" => Response::string(db::flush(payload, cid, manager).await?),
         "client/list" => Response::string(client::list(payload, cid, manager).await?),
         "client/kill" => Response::string(client::kill(payload, cid, manager).await?),
+        "client/tracking" => Response::string(client::tracking(window, pubsub, payload, cid, manager).await?),
         "config/databases" => Response::string(config::get_database(cid, manager).await?),
         "config/all" => Response::string(config::get_all(cid, manager).await?),
         "config/edit" => Response::string(config::edit(payload, cid, manager).await?),
         "config/rewrite" => Response::string(config::rewrite(cid, manager).await?),
         "config/resetstat" => Response::string(config::reset_stat(cid, manager).await?),
-        "pubsub/subscribe" => Response::string(pubsub::subscribe(window, pubsub, payload, cid).await?),
+        "pubsub/subscribe" => Response::string(pubsub::subscribe(window, pubsub, manager, payload, cid).await?),
         "pubsub/publish" => Response::string(pubsub::publish(payload, cid, manager).await?),
         "pubsub/cancel" => Response::string(pubsub::cancel(payload, pubsub).await?),
-        "pubsub/monitor" => Response::string(pubsub::monitor(window, pubsub, cid).await?),
+        "pubsub/change" => Response::string(pubsub::change(payload, pubsub).await?),
+        "pubsub/channels" => Response::string(pubsub::channels(payload, cid, manager).await?),
+        "pubsub/replay" => Response::string(pubsub::replay(window, pubsub, manager, payload, cid).await?),
+        "script/run" => Response::string(script::run(window, pubsub, manager, payload, cid).await?),
+        "pubsub/monitor" => Response::string(pubsub::monitor(window, pubsub, payload, cid).await?),
+        "keyspace/watch" => Response::string(keyspace::watch(window, pubsub, manager, payload, cid).await?),
         "cluster/scan" => Response::string(cluster::scan(cid, payload, manager).await?),
         "cluster/nodes" => Response::string(cluster::node(cid, manager).await?),
+        "cluster/shards" => Response::string(cluster::shards(cid, manager).await?),
+        "cluster/coverage" => Response::string(cluster::coverage(cid, manager).await?),
         "cluster/nodesize" => Response::string(cluster::node_size(cid, payload, manager).await?),
         "cluster/analysis" => Response::string(cluster::analysis(cid, payload, manager).await?),
         "debug/log" => Response::string(debug::log(manager, window).await?),
-        "debug/cancel" => Response::string(debug::cancel(manager).await?),
+        "logs/recent" => Response::string(logs::recent(payload).await?),
+        "logs/level" => Response::string(logs::level().await?),
+        "logs/level/set" => Response::string(logs::set_level(payload).await?),
+        "debug/cancel" => Response::string(debug::cancel(payload).await?),
+        "debug/persist" => Response::string(debug::persist().await?),
+        "debug/persist/set" => Response::string(debug::set_persist(payload).await?),
+        "debug/history" => Response::string(debug::history(payload).await?),
+        "debug/history/clear" => Response::string(debug::clear().await?),
+        "debug/export" => Response::string(debug::export(payload).await?),
         "debug/clients" => Response::string(debug::clients(manager, pubsub).await?),
         "transfer/php_unserialize" => Response::string(transfer::php_unserialize(payload).await?),
+        "transfer/php_serialize" => Response::string(transfer::php_serialize(payload).await?),
         "json/set" => Response::string(json::set(payload, cid, manager).await?),
 
         "topk/list" => Response::string(topk::list(payload, cid, manager).await?),
@@ -275,10 +340,21 @@ pub async fn dispatch(
 
         "terminal/open" => Response::string(terminal::open(cid, window, manager, event_manage).await?),
         "terminal/cancel" => Response::string(terminal::cancel(payload, window, event_manage).await?),
+        "terminal/complete" => Response::string(terminal::complete(payload, cid, manager).await?),
+        "terminal/history" => Response::string(terminal::history(payload, cid).await?),
+        "terminal/history/step" => Response::string(terminal::history_step(payload, cid).await?),
+        "terminal/history/clear" => Response::string(terminal::history_clear(cid).await?),
+        "snippets" => Response::string(snippet::all(cid).await?),
+        "snippets/save" => Response::string(snippet::save(payload).await?),
+        "snippets/del" => Response::string(snippet::del(payload).await?),
+        "snippets/expand" => Response::string(snippet::expand(payload, cid).await?),
 
         "collections" => Response::string(collection::all().await?),
         "collections/add" => Response::string(collection::add(payload).await?),
         "collections/del" => Response::string(collection::del(payload).await?),
+        "recordings" => Response::string(recording::all().await?),
+        "recordings/messages" => Response::string(recording::messages(payload).await?),
+        "recordings/del" => Response::string(recording::del(payload).await?),
 
         _ => Err(CusError::App(format!("{} Not Found", path))),
     };
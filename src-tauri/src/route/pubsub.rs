@@ -1,108 +1,626 @@
-use crate::connection::{Connectable, Connection, Manager};
+use crate::connection::{
+    Connectable, Connection, ConnectionWrapper, Manager, Node, WRITE_CONFIRMED,
+};
 use crate::err::CusError;
-use crate::pubsub::{PubsubItem, PubsubManager};
+use crate::monitor::{MonitorEvent, MonitorFilter, MonitorLine, MonitorState};
+use crate::pubsub::{PayloadEncoding, PubsubChange, PubsubItem, PubsubManager};
 use crate::response::EventResp;
-use crate::sqlite::Connection as ConnectionModel;
+use crate::sqlite::{Connection as ConnectionModel, RecordArgs, Recorder, Recording};
 use crate::utils;
 use futures::StreamExt;
 
-use redis::FromRedisValue;
+use redis::aio::PubSubSink;
+use redis::cluster_routing::get_slot;
+use redis::{FromRedisValue, PushInfo, PushKind};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use tauri::Emitter;
+use tauri::Manager as _;
 use tauri::State;
 
-use tokio::sync::oneshot;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tokio::sync::{mpsc, oneshot};
 
 #[derive(Deserialize)]
 struct SubscribeArgs {
+    #[serde(default)]
     channels: Vec<String>,
+    // glob-style patterns, PSUBSCRIBE
+    #[serde(default)]
+    patterns: Vec<String>,
+    // sharded channels, SSUBSCRIBE on the node owning the slot of the channel
+    #[serde(default)]
+    shard_channels: Vec<String>,
+    #[serde(default)]
+    encoding: PayloadEncoding,
+    // capture the messages to sqlite
+    record: Option<RecordArgs>,
 }
 #[derive(Serialize, Debug)]
 struct Message {
     channel: String,
     payload: String,
+    // the pattern matched the channel
+    pattern: Option<String>,
+    sharded: bool,
+}
+
+// the channels subscribed by one subscription
+// the shared connection receives the messages of other subscriptions, they are filtered out
+#[derive(Default)]
+struct Channels {
+    channels: HashSet<String>,
+    patterns: HashSet<String>,
+    shard_channels: HashSet<String>,
+}
+
+impl Channels {
+    fn get_mut(&mut self, cmd: &str) -> Option<(&mut HashSet<String>, bool)> {
+        match cmd {
+            "subscribe" => Some((&mut self.channels, true)),
+            "unsubscribe" => Some((&mut self.channels, false)),
+            "psubscribe" => Some((&mut self.patterns, true)),
+            "punsubscribe" => Some((&mut self.patterns, false)),
+            "ssubscribe" => Some((&mut self.shard_channels, true)),
+            "sunsubscribe" => Some((&mut self.shard_channels, false)),
+            _ => None,
+        }
+    }
+
+    // the message of the subscribed channels
+    fn parse(&self, info: &PushInfo) -> Result<Option<RawMessage>, CusError> {
+        let (channel, pattern, payload, sharded) = match (&info.kind, info.data.as_slice()) {
+            // message: [channel, payload]
+            (PushKind::Message, [channel, payload]) => (channel, None, payload, false),
+            // pmessage: [pattern, channel, payload]
+            (PushKind::PMessage, [pattern, channel, payload]) => (
+                channel,
+                Some(String::from_redis_value(pattern)?),
+                payload,
+                false,
+            ),
+            (PushKind::SMessage, [channel, payload]) => (channel, None, payload, true),
+            _ => return Ok(None),
+        };
+        let message = RawMessage {
+            channel: String::from_redis_value(channel)?,
+            pattern,
+            sharded,
+            payload: Vec::<u8>::from_redis_value(payload)?,
+        };
+        let subscribed = match (&message.pattern, message.sharded) {
+            (Some(pattern), _) => self.patterns.contains(pattern),
+            (None, true) => self.shard_channels.contains(&message.channel),
+            (None, false) => self.channels.contains(&message.channel),
+        };
+        Ok(subscribed.then_some(message))
+    }
+}
+
+// the message before the payload encoded
+struct RawMessage {
+    channel: String,
+    pattern: Option<String>,
+    sharded: bool,
+    payload: Vec<u8>,
+}
+
+impl RawMessage {
+    fn record(&self, recorder: &Option<Recorder>) {
+        if let Some(recorder) = recorder {
+            recorder.record(
+                Some(&self.channel),
+                self.pattern.as_deref(),
+                self.sharded,
+                &self.payload,
+            );
+        }
+    }
+
+    fn encode(self, encoding: PayloadEncoding) -> Message {
+        Message {
+            channel: self.channel,
+            payload: encoding.encode(self.payload),
+            pattern: self.pattern,
+            sharded: self.sharded,
+        }
+    }
+}
+
+// the connections of a subscription, the messages of all connections are received as push
+struct Subscriber {
+    // subscribe and psubscribe are sent to it
+    main: Arc<ConnectionWrapper>,
+    // the main connection is the opened connection, it must be unsubscribed when closed
+    shared: bool,
+    // the masters of cluster, sharded channels are subscribed on the owner
+    nodes: Vec<Node>,
+    // the connections of the masters by node id
+    shards: HashMap<String, Arc<ConnectionWrapper>>,
+    push_tx: mpsc::UnboundedSender<PushInfo>,
+    channels: Channels,
+}
+
+impl Subscriber {
+    fn new(
+        main: Arc<ConnectionWrapper>,
+        shared: bool,
+        nodes: Vec<Node>,
+        push_tx: mpsc::UnboundedSender<PushInfo>,
+    ) -> Result<Self, CusError> {
+        forward_push(main.subscribe_push()?, push_tx.clone());
+        Ok(Self {
+            main,
+            shared,
+            nodes,
+            shards: HashMap::new(),
+            push_tx,
+            channels: Channels::default(),
+        })
+    }
+
+    // the connection of the node owning the slot of the sharded channel
+    async fn get_shard(&mut self, channel: &str) -> Result<Arc<ConnectionWrapper>, CusError> {
+        if self.nodes.is_empty() {
+            return Ok(self.main.clone());
+        }
+        let slot = get_slot(channel.as_bytes());
+        let node = self
+            .nodes
+            .iter()
+            .find(|n| n.slots.iter().any(|r| r.start <= slot && slot <= r.end))
+            .ok_or_else(|| CusError::App(format!("No node serves the slot {}", slot)))?;
+        if let Some(conn) = self.shards.get(&node.id) {
+            return Ok(conn.clone());
+        }
+        let mut params = node.get_params();
+        params.redis_params.protocol = redis::ProtocolVersion::RESP3;
+        let conn = Arc::new(ConnectionWrapper::build(params).await?);
+        forward_push(conn.subscribe_push()?, self.push_tx.clone());
+        self.shards.insert(node.id.clone(), conn.clone());
+        Ok(conn)
+    }
+
+    async fn send(&mut self, cmd: &str, channels: Vec<String>) -> Result<(), CusError> {
+        // the shared connection counts the subscriptions of a channel,
+        // so only the changes of this subscription are sent
+        let (set, add) = self
+            .channels
+            .get_mut(cmd)
+            .ok_or_else(|| CusError::App(format!("invalid command: {}", cmd)))?;
+        let channels: Vec<String> = channels
+            .into_iter()
+            .filter(|c| set.contains(c) != add)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if cmd == "ssubscribe" || cmd == "sunsubscribe" {
+            for channel in &channels {
+                let conn = self.get_shard(channel).await?;
+                conn.send_pubsub(cmd, std::slice::from_ref(channel)).await?;
+            }
+        } else {
+            self.main.send_pubsub(cmd, &channels).await?;
+        }
+        let (set, add) = self
+            .channels
+            .get_mut(cmd)
+            .ok_or_else(|| CusError::App(format!("invalid command: {}", cmd)))?;
+        for channel in channels {
+            if add {
+                set.insert(channel);
+            } else {
+                set.remove(&channel);
+            }
+        }
+        Ok(())
+    }
+
+    // the dedicated connections are closed when dropped
+    async fn close(mut self) {
+        if !self.shared {
+            return;
+        }
+        let channels = std::mem::take(&mut self.channels);
+        let _ = self
+            .main
+            .send_pubsub("unsubscribe", &Vec::from_iter(channels.channels))
+            .await;
+        let _ = self
+            .main
+            .send_pubsub("punsubscribe", &Vec::from_iter(channels.patterns))
+            .await;
+        let _ = self
+            .main
+            .send_pubsub("sunsubscribe", &Vec::from_iter(channels.shard_channels))
+            .await;
+    }
+}
+
+// the receiver stops when the connection is dropped
+fn forward_push(mut rx: Receiver<PushInfo>, tx: mpsc::UnboundedSender<PushInfo>) {
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(info) => {
+                    if tx.send(info).is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+// the server before 6.0 does not support RESP3
+async fn support_resp3(manager: &Manager, cid: u32) -> bool {
+    match manager.get_version(cid).await {
+        Ok(version) => utils::compare_version(&version, "6.0.0") >= 0,
+        Err(_) => false,
+    }
 }
 
 pub async fn subscribe(
     window: tauri::Window,
     pubsub_manager: State<'_, PubsubManager>,
+    manager: State<'_, Manager>,
     payload: String,
     cid: u32,
 ) -> Result<String, CusError> {
     let args: SubscribeArgs = serde_json::from_str(&payload)?;
+    let conn = match manager.get(cid).await {
+        Ok(conn) => conn,
+        Err(_) => return subscribe_dedicated(window, pubsub_manager, cid, args).await,
+    };
+    // the opened RESP3 connection receives the messages as push, no extra connection is needed
+    let (main, shared) = if conn.subscribe_push().is_ok() {
+        (conn.clone(), true)
+    } else if support_resp3(&manager, cid).await {
+        let mut params = conn.model.origin.clone();
+        params.is_cluster = false;
+        params.read_from_replica = false;
+        params.replica_params = None;
+        params.redis_params.protocol = redis::ProtocolVersion::RESP3;
+        (Arc::new(ConnectionWrapper::build(params).await?), false)
+    } else {
+        return subscribe_dedicated(window, pubsub_manager, cid, args).await;
+    };
+    let nodes = if conn.is_cluster() {
+        manager
+            .get_nodes(cid)
+            .await?
+            .into_iter()
+            .filter(|n| n.is_master() && !n.is_failed())
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let encoding = args.encoding;
+    let recorder = match args.record {
+        Some(record) => Some(Recorder::start(cid, "pubsub", record)?),
+        None => None,
+    };
+    let (push_tx, mut push_rx) = mpsc::unbounded_channel::<PushInfo>();
+    let mut subscriber = Subscriber::new(main, shared, nodes, push_tx)?;
+    let subscribe = [
+        ("subscribe", args.channels),
+        ("psubscribe", args.patterns),
+        ("ssubscribe", args.shard_channels),
+    ];
+    for (cmd, channels) in subscribe {
+        if !channels.is_empty() {
+            if let Err(e) = subscriber.send(cmd, channels).await {
+                subscriber.close().await;
+                return Err(e);
+            }
+        }
+    }
 
     let event_name = utils::random_str(32);
     let event_name_resp = event_name.clone();
-    let model = ConnectionModel::first(cid)?;
     let (tx, rx) = oneshot::channel::<()>();
-    // a channel to stop loop when frontend close the page
-    let connection = Connection::new(model.get_params());
+    let (control_tx, mut control_rx) = mpsc::unbounded_channel::<PubsubChange>();
     pubsub_manager.add(
         event_name.clone(),
         PubsubItem::new(
             tx,
             event_name.clone(),
-            connection.get_host(),
+            subscriber.main.get_host(),
             "pubsub".to_string(),
-            connection.get_proxy(),
-        ),
+            subscriber.main.model.get_proxy(),
+        )
+        .with_control(control_tx),
     );
     tokio::spawn(async move {
-        let conn_result = connection.get_sync_one().await;
-        match conn_result {
-            Err(e) => {
-                println!("{}", e)
-            }
-            Ok(mut conn) => {
-                let mut subpub = conn.as_pubsub();
-                for x in args.channels {
-                    let result = subpub.subscribe(&x);
-                    match result {
-                        Err(e) => {
-                            println!("{}", e)
+        let event_str = event_name.as_str();
+        tokio::select! {
+            _ = async {
+                loop {
+                    tokio::select! {
+                        info = push_rx.recv() => {
+                            let info = match info {
+                                Some(info) => info,
+                                None => break,
+                            };
+                            if let Some(message) = subscriber.channels.parse(&info)? {
+                                message.record(&recorder);
+                                let r = EventResp::new(message.encode(encoding), String::from(event_str));
+                                let _ = window.emit(event_str, serde_json::to_string(&r)?);
+                            }
                         }
-                        Ok(_) => {
-                            println!("ok")
+                        Some(change) = control_rx.recv() => {
+                            let r = subscriber.send(&change.cmd, change.channels).await;
+                            let _ = change.reply.send(r);
                         }
                     }
                 }
-                let event_str = event_name.as_str();
-                tokio::select! {
-                    _ = async {
-                        loop {
-                            let msg = subpub.get_message();
-                            if let Ok(message) = msg {
-                                let payload = message.get_payload::<String>()?;
-                                let r: EventResp<Message> = EventResp::new(
-                                    Message {
-                                        channel: message.get_channel_name().to_string(),
-                                        payload,
-                                    },
-                                    String::from(event_str),
-                                );
-                                let _ = window.emit(event_str, serde_json::to_string(&r)?);
-                            } else {
-                                break
-                            }
+                Ok::<(), CusError>(())
+            } => {},
+            _ = rx => {}
+        }
+        subscriber.close().await;
+    });
+    Ok(event_name_resp)
+}
+
+// the server does not support RESP3, a dedicated RESP2 connection is used
+// sharded channels are not supported by RESP2 pubsub
+async fn subscribe_dedicated(
+    window: tauri::Window,
+    pubsub_manager: State<'_, PubsubManager>,
+    cid: u32,
+    args: SubscribeArgs,
+) -> Result<String, CusError> {
+    if !args.shard_channels.is_empty() {
+        return Err(CusError::build("Sharded pub/sub requires Redis 7.0"));
+    }
+    let model = ConnectionModel::first(cid)?;
+    let mut params = model.get_params();
+    params.is_cluster = false;
+    params.read_from_replica = false;
+    params.replica_params = None;
+    params.redis_params.protocol = redis::ProtocolVersion::RESP2;
+    let mut connection = Connection::new(params);
+    connection.open_tunnel().await?;
+    let mut pubsub = connection.get_pubsub().await?;
+    let mut channels = Channels::default();
+    for channel in args.channels {
+        pubsub.subscribe(&channel).await?;
+        channels.channels.insert(channel);
+    }
+    for pattern in args.patterns {
+        pubsub.psubscribe(&pattern).await?;
+        channels.patterns.insert(pattern);
+    }
+    let (mut sink, mut stream) = pubsub.split();
+
+    let event_name = utils::random_str(32);
+    let event_name_resp = event_name.clone();
+    // a channel to stop loop when frontend close the page
+    let (tx, rx) = oneshot::channel::<()>();
+    let (control_tx, mut control_rx) = mpsc::unbounded_channel::<PubsubChange>();
+    let encoding = args.encoding;
+    let recorder = match args.record {
+        Some(record) => Some(Recorder::start(cid, "pubsub", record)?),
+        None => None,
+    };
+    // registered after every fallible step, so a failed request is never listed
+    pubsub_manager.add(
+        event_name.clone(),
+        PubsubItem::new(
+            tx,
+            event_name.clone(),
+            connection.get_host(),
+            "pubsub".to_string(),
+            connection.get_proxy(),
+        )
+        .with_control(control_tx),
+    );
+    tokio::spawn(async move {
+        let event_str = event_name.as_str();
+        tokio::select! {
+            _ = async {
+                loop {
+                    tokio::select! {
+                        msg = stream.next() => {
+                            let msg = match msg {
+                                Some(msg) => msg,
+                                None => break,
+                            };
+                            let pattern = match msg.from_pattern() {
+                                true => Some(msg.get_pattern::<String>()?),
+                                false => None,
+                            };
+                            let message = RawMessage {
+                                channel: msg.get_channel_name().to_string(),
+                                pattern,
+                                sharded: false,
+                                payload: msg.get_payload_bytes().to_vec(),
+                            };
+                            message.record(&recorder);
+                            let r = EventResp::new(message.encode(encoding), String::from(event_str));
+                            let _ = window.emit(event_str, serde_json::to_string(&r)?);
+                        }
+                        Some(change) = control_rx.recv() => {
+                            let r = change_dedicated(&mut sink, &mut channels, &change.cmd, change.channels).await;
+                            let _ = change.reply.send(r);
                         }
-                        Ok::<(), CusError>(())
-                    } => {},
-                    _ = rx => {
                     }
                 }
-            }
+                Ok::<(), CusError>(())
+            } => {},
+            _ = rx => {}
         }
+        drop(stream);
         drop(connection);
     });
     Ok(event_name_resp)
 }
 
+async fn change_dedicated(
+    sink: &mut PubSubSink,
+    channels: &mut Channels,
+    cmd: &str,
+    names: Vec<String>,
+) -> Result<(), CusError> {
+    for name in &names {
+        match cmd {
+            "subscribe" => sink.subscribe(name).await?,
+            "unsubscribe" => sink.unsubscribe(name).await?,
+            "psubscribe" => sink.psubscribe(name).await?,
+            "punsubscribe" => sink.punsubscribe(name).await?,
+            "ssubscribe" | "sunsubscribe" => {
+                return Err(CusError::build("Sharded pub/sub requires Redis 7.0"))
+            }
+            _ => return Err(CusError::App(format!("invalid command: {}", cmd))),
+        }
+    }
+    if let Some((set, add)) = channels.get_mut(cmd) {
+        for name in names {
+            if add {
+                set.insert(name);
+            } else {
+                set.remove(&name);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ChangeArgs {
+    name: String,
+    cmd: String,
+    channels: Vec<String>,
+}
+
+// add or remove channels of a live subscription
+pub async fn change(
+    payload: String,
+    pubsub_manager: State<'_, PubsubManager>,
+) -> Result<String, CusError> {
+    let args: ChangeArgs = serde_json::from_str(&payload)?;
+    pubsub_manager
+        .change(&args.name, args.cmd.to_lowercase(), args.channels)
+        .await?;
+    Ok(String::from("OK"))
+}
+
+#[derive(Deserialize)]
+struct ChannelsArgs {
+    #[serde(default = "default_pattern")]
+    pattern: String,
+}
+
+fn default_pattern() -> String {
+    String::from("*")
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChannelStat {
+    channel: String,
+    // the sum of the subscribers on every node
+    subscribers: i64,
+    sharded: bool,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct PubsubStat {
+    channels: Vec<ChannelStat>,
+    // the number of the pattern subscriptions
+    patterns: i64,
+}
+
+// the active channels with subscriber counts
+// every node of cluster is asked, the subscribers are local to the node
+pub async fn channels(
+    payload: String,
+    cid: u32,
+    manager: State<'_, Manager>,
+) -> Result<PubsubStat, CusError> {
+    let args: ChannelsArgs = serde_json::from_str(&payload)?;
+    let conn = manager.get(cid).await?;
+    let mut targets: Vec<Arc<ConnectionWrapper>> = vec![];
+    if conn.is_cluster() {
+        for node in manager.get_nodes(cid).await? {
+            if !node.is_failed() {
+                targets.push(Arc::new(manager.connect_to(node).await?));
+            }
+        }
+    } else {
+        targets.push(conn.clone());
+        if let Some(replica) = &conn.replica {
+            targets.push(Arc::new(
+                ConnectionWrapper::build(replica.model.get_params()).await?,
+            ));
+        }
+    }
+    let mut stat = PubsubStat::default();
+    let mut counts: BTreeMap<(String, bool), i64> = BTreeMap::new();
+    for target in targets {
+        let channels: Vec<String> = manager
+            .execute_with(
+                redis::cmd("PUBSUB").arg("CHANNELS").arg(&args.pattern),
+                &target,
+            )
+            .await?;
+        if !channels.is_empty() {
+            let numsub: HashMap<String, i64> = manager
+                .execute_with(redis::cmd("PUBSUB").arg("NUMSUB").arg(&channels), &target)
+                .await?;
+            for (channel, n) in numsub {
+                *counts.entry((channel, false)).or_default() += n;
+            }
+        }
+        // sharded pub/sub is added in 7.0
+        let shard_channels: Vec<String> = manager
+            .execute_with(
+                redis::cmd("PUBSUB").arg("SHARDCHANNELS").arg(&args.pattern),
+                &target,
+            )
+            .await
+            .unwrap_or_default();
+        if !shard_channels.is_empty() {
+            let numsub: HashMap<String, i64> = manager
+                .execute_with(
+                    redis::cmd("PUBSUB").arg("SHARDNUMSUB").arg(&shard_channels),
+                    &target,
+                )
+                .await?;
+            for (channel, n) in numsub {
+                *counts.entry((channel, true)).or_default() += n;
+            }
+        }
+        let numpat: i64 = manager
+            .execute_with(redis::cmd("PUBSUB").arg("NUMPAT"), &target)
+            .await?;
+        stat.patterns += numpat;
+    }
+    stat.channels = counts
+        .into_iter()
+        .map(|((channel, sharded), subscribers)| ChannelStat {
+            channel,
+            subscribers,
+            sharded,
+        })
+        .collect();
+    Ok(stat)
+}
+
 #[derive(Deserialize)]
 struct PublishArgs {
     db: u8,
     channel: String,
     value: String,
+    // SPUBLISH to the sharded channel
+    #[serde(default)]
+    sharded: bool,
 }
 
 pub async fn publish(
@@ -111,28 +629,49 @@ pub async fn publish(
     manager: State<'_, Manager>,
 ) -> Result<i64, CusError> {
     let args: PublishArgs = serde_json::from_str(&payload)?;
+    let cmd = if args.sharded { "spublish" } else { "publish" };
     manager
         .execute(
             cid,
-            redis::cmd("publish").arg(args.channel).arg(args.value),
+            redis::cmd(cmd).arg(args.channel).arg(args.value),
             Some(args.db),
         )
         .await
 }
 
+#[derive(Deserialize)]
+struct MonitorArgs {
+    // capture the lines to sqlite
+    record: Option<RecordArgs>,
+    #[serde(default, flatten)]
+    filter: MonitorFilter,
+}
+
 pub async fn monitor(
     window: tauri::Window,
     pubsub_manager: State<'_, PubsubManager>,
+    payload: String,
     cid: u32,
 ) -> Result<String, CusError> {
+    let args: MonitorArgs = serde_json::from_str(&payload)?;
     let model = ConnectionModel::first(cid)?;
-    let connection = Connection::new(model.get_params());
+    // the dedicated connection always uses RESP2
+    let mut params = model.get_params();
+    params.redis_params.protocol = redis::ProtocolVersion::RESP2;
+    let mut connection = Connection::new(params);
+    connection.open_tunnel().await?;
 
     let event_name = utils::random_str(32);
     let event_name_resp = event_name.clone();
 
     // // a channel to stop loop when frontend close the page
     let (tx, rx) = oneshot::channel::<()>();
+    let mut monitor = connection.get_monitor().await?;
+    let recorder = match args.record {
+        Some(record) => Some(Recorder::start(cid, "monitor", record)?),
+        None => None,
+    };
+    // registered after every fallible step, so a failed request is never listed
     pubsub_manager.add(
         event_name_resp.clone(),
         PubsubItem::new(
@@ -143,23 +682,58 @@ pub async fn monitor(
             connection.get_proxy(),
         ),
     );
-    let mut monitor = connection.get_monitor().await?;
+    let mut state = MonitorState::new(args.filter);
     tokio::spawn(async move {
         let event_str = event_name.as_str();
         let _ = monitor.monitor().await;
         let mut stream = monitor.into_on_message();
+        let emit = |event: MonitorEvent| -> Result<(), CusError> {
+            let r = EventResp::new(event, String::from(event_str));
+            let _ = window.emit(event_str, serde_json::to_string(&r)?);
+            Ok(())
+        };
+        let mut ticker = state.stats_interval().map(|d| {
+            let mut t = tokio::time::interval_at(tokio::time::Instant::now() + d, d);
+            t.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            t
+        });
         tokio::select! {
             _ = async {
                 loop {
-                    if let Some(msg) = stream.next().await {
-                        let msg_string =  String::from_redis_value(&msg)?;
-                        let r: EventResp<String> = EventResp::new(
-                            msg_string,
-                            String::from(event_str),
-                        );
-                        let _ = window.emit(event_str, serde_json::to_string(&r)?);
-                    } else {
-                        break;
+                    let tick = async {
+                        match ticker.as_mut() {
+                            Some(t) => {
+                                t.tick().await;
+                            }
+                            None => std::future::pending::<()>().await,
+                        }
+                    };
+                    tokio::select! {
+                        msg = stream.next() => {
+                            let Some(msg) = msg else {
+                                break;
+                            };
+                            let msg_string = String::from_redis_value(&msg)?;
+                            // the recording keeps every line, the filters only apply to the window
+                            if let Some(recorder) = &recorder {
+                                recorder.record(None, None, false, msg_string.as_bytes());
+                            }
+                            match MonitorLine::parse(&msg_string) {
+                                Some(line) => {
+                                    if let Some(line) = state.accept(line) {
+                                        emit(MonitorEvent::Line(line))?;
+                                    }
+                                }
+                                None => {
+                                    if let Some(raw) = state.accept_raw(msg_string) {
+                                        emit(MonitorEvent::Raw { raw })?;
+                                    }
+                                }
+                            }
+                        }
+                        _ = tick => {
+                            emit(MonitorEvent::Stats(state.take_stats()))?;
+                        }
                     }
                 }
                 Ok::<_, CusError>(())
@@ -168,10 +742,120 @@ pub async fn monitor(
             _ = rx => {
             }
         }
+        drop(connection);
     });
     Ok(event_name_resp)
 }
 
+#[derive(Deserialize)]
+struct ReplayArgs {
+    // the id of the recording
+    id: i64,
+    // 2 is twice as fast as recorded, 0 publishes without waiting
+    #[serde(default = "default_speed")]
+    speed: f64,
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+#[derive(Serialize, Debug)]
+struct ReplayProgress {
+    replayed: i64,
+    total: i64,
+    done: bool,
+    // the replay is stopped by the error
+    error: Option<String>,
+}
+
+// the messages loaded from sqlite at a time
+const REPLAY_BATCH: i64 = 500;
+
+// publish the recorded messages to the connection with the recorded intervals
+pub async fn replay(
+    window: tauri::Window,
+    pubsub_manager: State<'_, PubsubManager>,
+    manager: State<'_, Manager>,
+    payload: String,
+    cid: u32,
+) -> Result<String, CusError> {
+    let args: ReplayArgs = serde_json::from_str(&payload)?;
+    let recording = Recording::first(args.id)?;
+    if recording.types != "pubsub" {
+        return Err(CusError::build("Only pub/sub recordings can be replayed"));
+    }
+    let conn = manager.get(cid).await?;
+    manager.check_write(&redis::cmd("publish"), &conn).await?;
+    // the task publishes with the confirmation of the request
+    let confirmed = WRITE_CONFIRMED.try_with(|c| *c).unwrap_or(false);
+
+    let event_name = utils::random_str(32);
+    let event_name_resp = event_name.clone();
+    let (tx, rx) = oneshot::channel::<()>();
+    pubsub_manager.add(
+        event_name.clone(),
+        PubsubItem::new(
+            tx,
+            event_name.clone(),
+            conn.get_host(),
+            "replay".to_string(),
+            conn.model.get_proxy(),
+        ),
+    );
+    tokio::spawn(WRITE_CONFIRMED.scope(confirmed, async move {
+        let manager = window.state::<Manager>();
+        let event_str = event_name.as_str();
+        let started = Instant::now();
+        let mut replayed: i64 = 0;
+        let emit = |replayed: i64, done: bool, error: Option<String>| -> Result<(), CusError> {
+            let progress = ReplayProgress {
+                replayed,
+                total: recording.count,
+                done,
+                error,
+            };
+            let r = EventResp::new(progress, String::from(event_str));
+            let _ = window.emit(event_str, serde_json::to_string(&r)?);
+            Ok(())
+        };
+        let result = tokio::select! {
+            r = async {
+                loop {
+                    let messages = recording.messages(replayed, REPLAY_BATCH)?;
+                    if messages.is_empty() {
+                        break;
+                    }
+                    for m in messages {
+                        if args.speed > 0.0 {
+                            let at = Duration::from_micros((m.elapsed as f64 / args.speed) as u64);
+                            if let Some(wait) = at.checked_sub(started.elapsed()) {
+                                tokio::time::sleep(wait).await;
+                            }
+                        }
+                        if let Some(channel) = m.channel {
+                            let cmd = if m.sharded { "spublish" } else { "publish" };
+                            let mut publish = redis::cmd(cmd);
+                            publish.arg(channel).arg(m.payload);
+                            manager.execute_with::<i64>(&mut publish, &conn).await?;
+                        }
+                        replayed += 1;
+                        if replayed % 100 == 0 {
+                            emit(replayed, false, None)?;
+                        }
+                    }
+                }
+                Ok::<(), CusError>(())
+            } => r,
+            _ = rx => Ok(()),
+        };
+        // finished or failed, the replay is no longer listed
+        window.state::<PubsubManager>().close(&event_name);
+        let _ = emit(replayed, true, result.err().map(|e| e.to_string()));
+    }));
+    Ok(event_name_resp)
+}
+
 #[derive(Deserialize)]
 struct CancelArgs {
     name: String,
@@ -0,0 +1,52 @@
+use crate::{err::CusError, pubsub::PayloadEncoding, request::IdArgs, sqlite::Recording};
+use serde::{Deserialize, Serialize};
+
+pub async fn all() -> Result<Vec<Recording>, CusError> {
+    Recording::all()
+}
+
+#[derive(Deserialize)]
+struct MessagesArgs {
+    id: i64,
+    #[serde(default)]
+    offset: i64,
+    #[serde(default = "default_limit")]
+    limit: i64,
+    #[serde(default)]
+    encoding: PayloadEncoding,
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+#[derive(Serialize)]
+pub struct Message {
+    // milliseconds since the recording started
+    elapsed: f64,
+    channel: Option<String>,
+    pattern: Option<String>,
+    sharded: bool,
+    payload: String,
+}
+
+pub async fn messages(payload: String) -> Result<Vec<Message>, CusError> {
+    let args: MessagesArgs = serde_json::from_str(&payload)?;
+    let recording = Recording::first(args.id)?;
+    Ok(recording
+        .messages(args.offset, args.limit)?
+        .into_iter()
+        .map(|m| Message {
+            elapsed: m.elapsed as f64 / 1000.0,
+            channel: m.channel,
+            pattern: m.pattern,
+            sharded: m.sharded,
+            payload: args.encoding.encode(m.payload),
+        })
+        .collect())
+}
+
+pub async fn del(payload: String) -> Result<(), CusError> {
+    let args: IdArgs<i64> = serde_json::from_str(&payload)?;
+    Recording::first(args.id)?.del()
+}
@@ -0,0 +1,353 @@
+use crate::connection::Manager;
+use crate::err::CusError;
+use crate::pubsub::{PubsubItem, PubsubManager};
+use crate::redact;
+use crate::response::EventResp;
+use crate::sqlite::Connection as ConnectionModel;
+use crate::utils;
+use redis::Value;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager as _, State};
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::sync::oneshot;
+
+// the errors kept in the summary
+const MAX_ERRORS: usize = 1000;
+// the limits of a RESP command, the bulk is limited like proto-max-bulk-len
+const MAX_ARGS: usize = 1024 * 1024;
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ScriptFormat {
+    // resp if the file starts with *
+    #[default]
+    Auto,
+    // redis-cli syntax, a command per line
+    Text,
+    // the raw protocol used by redis-cli --pipe
+    Resp,
+}
+
+#[derive(Deserialize)]
+struct RunArgs {
+    path: String,
+    #[serde(default)]
+    format: ScriptFormat,
+    db: Option<u8>,
+    // run the rest after a command failed
+    // otherwise the commands are sent one by one, nothing runs after the failed one
+    #[serde(default)]
+    continue_on_error: bool,
+    // the commands read and reported together, pipelined if continue_on_error
+    #[serde(default = "default_chunk")]
+    chunk: usize,
+}
+
+fn default_chunk() -> usize {
+    1000
+}
+
+#[derive(Serialize, Debug)]
+struct ScriptError {
+    // the line of the text, the index of the command in resp
+    line: u64,
+    command: String,
+    error: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+struct ScriptProgress {
+    sent: u64,
+    succeeded: u64,
+    failed: u64,
+    read_bytes: u64,
+    total_bytes: u64,
+    // the errors since the last progress
+    errors: Vec<ScriptError>,
+    done: bool,
+    // stopped by the error
+    stopped: bool,
+}
+
+struct ScriptCommand {
+    line: u64,
+    args: Result<Vec<Vec<u8>>, String>,
+}
+
+impl ScriptCommand {
+    // the command shown in the error
+    fn text(&self) -> String {
+        match &self.args {
+            Ok(args) => {
+                let mut args: Vec<String> = args
+                    .iter()
+                    .map(|a| String::from_utf8_lossy(a).to_string())
+                    .collect();
+                redact::args(&mut args);
+                let s = args.join(" ");
+                s.chars().take(200).collect()
+            }
+            Err(_) => String::new(),
+        }
+    }
+}
+
+struct ScriptReader {
+    reader: BufReader<File>,
+    format: ScriptFormat,
+    line: u64,
+    read_bytes: u64,
+}
+
+impl ScriptReader {
+    async fn new(file: File, format: ScriptFormat) -> Result<Self, CusError> {
+        let mut reader = BufReader::new(file);
+        let format = match format {
+            ScriptFormat::Auto if reader.fill_buf().await?.first() == Some(&b'*') => {
+                ScriptFormat::Resp
+            }
+            ScriptFormat::Auto => ScriptFormat::Text,
+            f => f,
+        };
+        Ok(Self {
+            reader,
+            format,
+            line: 0,
+            read_bytes: 0,
+        })
+    }
+
+    async fn read_line(&mut self) -> Result<Option<Vec<u8>>, CusError> {
+        let mut buf = vec![];
+        let n = self.reader.read_until(b'\n', &mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        self.read_bytes += n as u64;
+        while buf.last().is_some_and(|c| *c == b'\n' || *c == b'\r') {
+            buf.pop();
+        }
+        Ok(Some(buf))
+    }
+
+    async fn next(&mut self) -> Result<Option<ScriptCommand>, CusError> {
+        match self.format {
+            ScriptFormat::Resp => self.next_resp().await,
+            _ => self.next_text().await,
+        }
+    }
+
+    // the empty lines and the comments start with # are skipped
+    async fn next_text(&mut self) -> Result<Option<ScriptCommand>, CusError> {
+        while let Some(buf) = self.read_line().await? {
+            self.line += 1;
+            let s = String::from_utf8_lossy(&buf);
+            let trimmed = s.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            return Ok(Some(ScriptCommand {
+                line: self.line,
+                args: utils::split_args(trimmed).ok_or_else(|| "Invalid argument(s)".to_string()),
+            }));
+        }
+        Ok(None)
+    }
+
+    // *<count>\r\n then $<len>\r\n<bytes>\r\n for every argument
+    async fn next_resp(&mut self) -> Result<Option<ScriptCommand>, CusError> {
+        let header = loop {
+            match self.read_line().await? {
+                Some(buf) if buf.is_empty() => continue,
+                Some(buf) => break buf,
+                None => return Ok(None),
+            }
+        };
+        self.line += 1;
+        let count = parse_header(&header, b'*', self.line, MAX_ARGS)?;
+        let mut args = vec![];
+        for _ in 0..count {
+            let header = self
+                .read_line()
+                .await?
+                .ok_or_else(|| resp_error(self.line))?;
+            let len = parse_header(&header, b'$', self.line, MAX_BULK_LEN)?;
+            // the bulk is followed by \r\n
+            let size = len.checked_add(2).ok_or_else(|| resp_error(self.line))?;
+            let mut arg = vec![0; size];
+            self.reader.read_exact(&mut arg).await?;
+            self.read_bytes += arg.len() as u64;
+            arg.truncate(len);
+            args.push(arg);
+        }
+        Ok(Some(ScriptCommand {
+            line: self.line,
+            args: Ok(args),
+        }))
+    }
+}
+
+fn resp_error(line: u64) -> CusError {
+    CusError::App(format!("Invalid RESP of the command {}", line))
+}
+
+// the number after the prefix, the larger one is rejected before allocated
+fn parse_header(buf: &[u8], prefix: u8, line: u64, max: usize) -> Result<usize, CusError> {
+    match buf.split_first() {
+        Some((p, n)) if *p == prefix => String::from_utf8_lossy(n)
+            .parse()
+            .ok()
+            .filter(|n| *n <= max)
+            .ok_or_else(|| resp_error(line)),
+        _ => Err(resp_error(line)),
+    }
+}
+
+// send the commands of the file in chunks, pipelined if the errors are ignored
+// the progress is emitted after every chunk, cancelled by pubsub/cancel
+pub async fn run(
+    window: tauri::Window,
+    pubsub_manager: State<'_, PubsubManager>,
+    manager: State<'_, Manager>,
+    payload: String,
+    cid: u32,
+) -> Result<String, CusError> {
+    let args: RunArgs = serde_json::from_str(&payload)?;
+    let readonly = ConnectionModel::first(cid)?.readonly;
+    let conn = manager.get(cid).await?;
+    // the script may write, so it is confirmed before started
+    if !readonly {
+        manager.check_confirmed(&conn)?;
+    }
+    let file = File::open(&args.path).await?;
+    let total_bytes = file.metadata().await?.len();
+    let mut reader = ScriptReader::new(file, args.format).await?;
+    let chunk = args.chunk.max(1);
+
+    let event_name = utils::random_str(32);
+    let event_name_resp = event_name.clone();
+    let (tx, rx) = oneshot::channel::<()>();
+    pubsub_manager.add(
+        event_name.clone(),
+        PubsubItem::new(
+            tx,
+            event_name.clone(),
+            conn.get_host(),
+            "script".to_string(),
+            conn.model.get_proxy(),
+        ),
+    );
+    tokio::spawn(async move {
+        let manager = window.state::<Manager>();
+        let event_str = event_name.as_str();
+        let mut progress = ScriptProgress {
+            total_bytes,
+            ..Default::default()
+        };
+        let mut reported: usize = 0;
+        let emit = |progress: &mut ScriptProgress| -> Result<(), CusError> {
+            let r = EventResp::new(&*progress, String::from(event_str));
+            let _ = window.emit(event_str, serde_json::to_string(&r)?);
+            progress.errors.clear();
+            Ok(())
+        };
+        let mut add_error =
+            |progress: &mut ScriptProgress, line: u64, command: String, error: String| {
+                progress.failed += 1;
+                if reported < MAX_ERRORS {
+                    reported += 1;
+                    progress.errors.push(ScriptError {
+                        line,
+                        command,
+                        error,
+                    });
+                }
+            };
+        let result = tokio::select! {
+            r = async {
+                let c = conn.get_conn(args.db).await?;
+                loop {
+                    let mut commands = vec![];
+                    while commands.len() < chunk {
+                        let Some(command) = reader.next().await? else {
+                            break;
+                        };
+                        match &command.args {
+                            Ok(v) if v.is_empty() => continue,
+                            Ok(v) => {
+                                let mut cmd = redis::cmd(&String::from_utf8_lossy(&v[0]));
+                                for arg in &v[1..] {
+                                    cmd.arg(arg.as_slice());
+                                }
+                                if readonly && manager.is_write(&cmd, &conn).await {
+                                    add_error(&mut progress, command.line, command.text(), "The connection is readonly".to_string());
+                                } else {
+                                    commands.push((command, cmd));
+                                }
+                            }
+                            Err(e) => {
+                                let e = e.clone();
+                                add_error(&mut progress, command.line, command.text(), e);
+                            }
+                        }
+                        if progress.failed > 0 && !args.continue_on_error {
+                            break;
+                        }
+                    }
+                    if commands.is_empty() && progress.errors.is_empty() {
+                        break;
+                    }
+                    let results = if args.continue_on_error {
+                        // the multiplexed connection pipelines the concurrent requests
+                        futures::future::join_all(commands.iter().map(|(_, cmd)| {
+                            let mut c = c.clone();
+                            async move { cmd.query_async::<Value>(&mut c).await }
+                        }))
+                        .await
+                    } else {
+                        // one by one, the commands after the failed one are not sent
+                        let mut results = vec![];
+                        let mut c = c.clone();
+                        for (_, cmd) in &commands {
+                            let result = cmd.query_async::<Value>(&mut c).await;
+                            let failed = result.is_err();
+                            results.push(result);
+                            if failed {
+                                break;
+                            }
+                        }
+                        results
+                    };
+                    progress.sent += results.len() as u64;
+                    for ((command, _), result) in commands.iter().zip(results) {
+                        match result {
+                            Ok(_) => progress.succeeded += 1,
+                            Err(e) => {
+                                let error = e.detail().map(String::from).unwrap_or(e.to_string());
+                                add_error(&mut progress, command.line, command.text(), error);
+                            }
+                        }
+                    }
+                    progress.read_bytes = reader.read_bytes;
+                    if progress.failed > 0 && !args.continue_on_error {
+                        progress.stopped = true;
+                        break;
+                    }
+                    emit(&mut progress)?;
+                }
+                Ok::<(), CusError>(())
+            } => r,
+            _ = rx => Ok(())
+        };
+        if let Err(e) = result {
+            add_error(&mut progress, reader.line, String::new(), e.to_string());
+        }
+        progress.read_bytes = reader.read_bytes;
+        progress.done = true;
+        window.state::<PubsubManager>().close(&event_name);
+        let _ = emit(&mut progress);
+    });
+    Ok(event_name_resp)
+}
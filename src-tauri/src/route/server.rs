@@ -21,9 +21,9 @@ pub async fn ping(
     manager: tauri::State<'_, Manager>,
 ) -> Result<String, CusError> {
     let params: Connection = serde_json::from_str(payload.as_str())?;
-    let mut conn = ConnectionWrapper::build(params).await?;
+    let conn = ConnectionWrapper::build(params).await?;
     let _ = manager
-        .execute_with::<String>(&mut redis::cmd("ping"), &mut conn)
+        .execute_with::<String>(&mut redis::cmd("ping"), &conn)
         .await?;
     Ok(String::from("PONG"))
 }
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{err::CusError, request::IdArgs, sqlite::Snippet};
+
+#[derive(Serialize)]
+pub struct SnippetItem {
+    #[serde(flatten)]
+    snippet: Snippet,
+    // the placeholders to fill
+    params: Vec<String>,
+}
+
+pub async fn all(cid: u32) -> Result<Vec<SnippetItem>, CusError> {
+    Ok(Snippet::all(cid)?
+        .into_iter()
+        .map(|snippet| SnippetItem {
+            params: snippet.params(),
+            snippet,
+        })
+        .collect())
+}
+
+pub async fn save(payload: String) -> Result<Snippet, CusError> {
+    let mut snippet: Snippet = serde_json::from_str(&payload)?;
+    snippet.save()?;
+    Ok(snippet)
+}
+
+pub async fn del(payload: String) -> Result<(), CusError> {
+    let args: IdArgs<i64> = serde_json::from_str(&payload)?;
+    Snippet::first(args.id)?.del()
+}
+
+#[derive(Deserialize)]
+struct ExpandArgs {
+    id: i64,
+    #[serde(default)]
+    params: HashMap<String, String>,
+}
+
+// the command line of the snippet, to be edited or run in the terminal
+pub async fn expand(payload: String, cid: u32) -> Result<String, CusError> {
+    let args: ExpandArgs = serde_json::from_str(&payload)?;
+    Ok(Snippet::first_of(args.id, cid)?.expand(&args.params))
+}
@@ -0,0 +1,23 @@
+use crate::{
+    err::CusError,
+    ssh::{self, PendingKey},
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct TrustArgs {
+    host: String,
+    port: u16,
+    fingerprint: String,
+}
+
+// the unknown host keys wait for trust
+pub fn pending() -> Result<Vec<PendingKey>, CusError> {
+    Ok(ssh::get_pending_keys())
+}
+
+// trust the host key on first use
+pub fn trust(payload: String) -> Result<(), CusError> {
+    let args: TrustArgs = serde_json::from_str(&payload)?;
+    ssh::trust_host_key(&args.host, args.port, &args.fingerprint)
+}
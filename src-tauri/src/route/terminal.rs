@@ -1,97 +1,522 @@
-use tauri::{Emitter, Event, Listener, Window};
+use tauri::{Emitter, Event, Listener, Manager as _, Window};
 
-use crate::connection::{CValue, EventManager, Manager};
+use crate::connection::{
+    CValue, Connectable, Connection, ConnectionParams, ConnectionWrapper, EventManager, Manager,
+    Node, WRITE_CONFIRMED,
+};
 use crate::err::CusError;
+use crate::model::Command;
+use crate::redact;
 use crate::request::IdArgs;
+use crate::sqlite::{Connection as ConnectionModel, History, Snippet};
 use crate::{response::EventResp, utils};
-use redis::{Cmd, RedisResult, Value as RedisValue};
-use std::cell::RefCell;
-#[derive(serde::Serialize)]
+use chrono::Local;
+use futures::StreamExt;
+use redis::cluster_routing::get_slot;
+use redis::{FromRedisValue, PushKind, Value as RedisValue};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
 
+#[derive(serde::Serialize)]
 pub struct OpenArgs {
     send: String,
     receive: String,
 }
 
-pub async fn open(
+// the commands block the connection until a reply, they run on a dedicated connection
+// WAIT and WAITAOF are not, they wait for the writes of the session connection
+const BLOCKING: [&str; 8] = [
+    "blpop",
+    "brpop",
+    "blmove",
+    "brpoplpush",
+    "blmpop",
+    "bzpopmin",
+    "bzpopmax",
+    "bzmpop",
+];
+
+const READING: &str = "Reading messages... (press Ctrl-C to quit)";
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Input {
+    // the line typed, split like redis-cli
+    Line(String),
+    Args(Vec<String>),
+    // stop the streaming or blocking command, like ctrl-c of redis-cli
+    Interrupt {
+        interrupt: bool,
+    },
+    // expand the snippet with the params and run it
+    Snippet {
+        snippet: i64,
+        #[serde(default)]
+        params: HashMap<String, String>,
+    },
+    // the answer to the write confirmation of the production connection
+    Confirm {
+        confirm: bool,
+    },
+}
+
+enum Mode {
+    Normal,
+    Blocking,
+    Subscribe,
+    Ssubscribe,
+    Monitor,
+    // XREAD/XREADGROUP with BLOCK, read again after every reply
+    Xread,
+}
+
+impl Mode {
+    fn build(name: &str, args: &[Vec<u8>]) -> Self {
+        match name {
+            "subscribe" | "psubscribe" => Mode::Subscribe,
+            "ssubscribe" => Mode::Ssubscribe,
+            "monitor" => Mode::Monitor,
+            "xread" | "xreadgroup" if args.iter().any(|a| a.eq_ignore_ascii_case(b"block")) => {
+                Mode::Xread
+            }
+            s if BLOCKING.contains(&s) => Mode::Blocking,
+            _ => Mode::Normal,
+        }
+    }
+}
+
+struct Session {
     cid: u32,
+    conn: ConnectionWrapper,
+    // the params to open the dedicated connections
+    params: ConnectionParams,
+    // the cluster nodes, to find the owner of the sharded channel
+    nodes: Vec<Node>,
+    // the db selected in the session
+    db: u8,
     window: Window,
-    manager: tauri::State<'_, Manager>,
-    event: tauri::State<'_, EventManager>,
-) -> Result<OpenArgs, CusError> {
-    let receive_event_name = utils::random_str(32);
-    let send_event_name = utils::random_str(32);
+    event: String,
+    // the line waiting for the write confirmation
+    pending: Option<String>,
+}
 
-    let inner_receive_event_name = receive_event_name.clone();
-    let inner_send_event_name = send_event_name.clone();
-    let window_copy = window.clone();
-    fn cmd_handle<F>(s: &str, func: F) -> EventResp<CValue>
-    where
-        F: Fn(Cmd) -> RedisResult<RedisValue>,
-    {
-        let item: EventResp<Vec<String>> = serde_json::from_str(s).unwrap();
-        let cmd_vec = item.data;
-        let mut resp_item: EventResp<CValue> = EventResp::new(CValue::Nil, String::new());
-        if let Some(first) = cmd_vec.first() {
-            if first.to_lowercase() == "monitor" || first.to_lowercase() == "subscribe" {
-                resp_item.success = false;
-                resp_item.data = CValue::Str("not support this command".to_string());
-            } else {
-                let mut cmd = redis::cmd(first);
-                let mut i = 1;
-                while i < cmd_vec.len() {
-                    cmd.arg(cmd_vec.get(i).unwrap());
-                    i += 1;
+impl Session {
+    fn emit(&self, success: bool, data: CValue) {
+        let mut resp_item = EventResp::new(data, self.event.clone());
+        resp_item.success = success;
+        let _ = self.window.emit(self.event.as_str(), &resp_item);
+    }
+
+    fn emit_error(&self, message: String) {
+        self.emit(false, CValue::Str(message));
+    }
+
+    // the dedicated connection never times out, it is closed when interrupted
+    async fn dedicated(&self) -> Result<ConnectionWrapper, CusError> {
+        let mut params = self.params.clone();
+        params.response_timeout = None;
+        ConnectionWrapper::build(params).await
+    }
+
+    // the connection with RESP2, for pubsub and monitor
+    async fn dedicated_resp2(&self) -> Result<Connection, CusError> {
+        let mut params = self.params.clone();
+        params.is_cluster = false;
+        params.redis_params.protocol = redis::ProtocolVersion::RESP2;
+        let mut connection = Connection::new(params);
+        connection.open_tunnel().await?;
+        Ok(connection)
+    }
+
+    async fn handle(&mut self, payload: &str, rx: &mut mpsc::UnboundedReceiver<String>) {
+        let line = match parse_input(payload) {
+            Some(Input::Line(line)) => line,
+            Some(Input::Args(args)) => args
+                .iter()
+                .map(|a| utils::quote_arg(a))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Some(Input::Snippet { snippet, params }) => {
+                match Snippet::first_of(snippet, self.cid) {
+                    Ok(snippet) => snippet.expand(&params),
+                    Err(e) => return self.emit_error(e.to_string()),
                 }
-                let result = func(cmd);
-                match result {
-                    Ok(vv) => {
-                        resp_item.data = CValue::build(vv);
+            }
+            Some(Input::Confirm { confirm }) => {
+                let Some(line) = self.pending.take() else {
+                    return;
+                };
+                if !confirm {
+                    return self.emit_error("Canceled".to_string());
+                }
+                return WRITE_CONFIRMED.scope(true, self.run(line, rx)).await;
+            }
+            // nothing is running
+            Some(Input::Interrupt { .. }) => return,
+            None => return self.emit_error("Invalid argument(s)".to_string()),
+        };
+        self.pending = None;
+        self.run(line, rx).await
+    }
+
+    async fn run(&mut self, line: String, rx: &mut mpsc::UnboundedReceiver<String>) {
+        let Some(args) = utils::split_args(&line) else {
+            return self.emit_error("Invalid argument(s)".to_string());
+        };
+        let Some(first) = args.first() else {
+            return self.emit_error("invalid args".to_string());
+        };
+        let name = String::from_utf8_lossy(first).to_lowercase();
+        let mut cmd = redis::cmd(&name);
+        for arg in &args[1..] {
+            cmd.arg(arg.as_slice());
+        }
+        // the write to the production connection waits for the confirmation, like the requests
+        let manager = self.window.state::<Manager>();
+        if let Err(e) = manager.check_write(&cmd, &self.conn).await {
+            if matches!(e, CusError::ConfirmRequired) {
+                self.pending = Some(line);
+            }
+            return self.emit_error(e.to_string());
+        }
+        let db = self.db;
+        let start = Local::now();
+        let result = match Mode::build(&name, &args) {
+            Mode::Normal => {
+                let result = self.normal(&cmd).await;
+                if result.is_ok() && name == "select" {
+                    if let Some(db) = args
+                        .get(1)
+                        .and_then(|v| String::from_utf8_lossy(v).parse().ok())
+                    {
+                        self.db = db;
                     }
-                    Err(e) => {
-                        if let Some(s) = e.detail() {
-                            resp_item.success = false;
-                            resp_item.data = CValue::Str(s.to_string())
-                        }
+                }
+                result.map(Some)
+            }
+            mode => {
+                let session = &*self;
+                let streaming = async {
+                    match mode {
+                        Mode::Subscribe => session.subscribe(&name, &args[1..]).await,
+                        Mode::Ssubscribe => session.ssubscribe(&args[1..]).await,
+                        Mode::Monitor => session.monitor().await,
+                        Mode::Xread => session.xread(&name, args.clone()).await,
+                        _ => return session.blocking(&cmd).await.map(Some),
                     }
+                    .map(|_| None)
+                };
+                tokio::select! {
+                    r = streaming => r,
+                    _ = session.wait_interrupt(rx) => Ok(None),
                 }
             }
+        };
+        let end = Local::now();
+        let duration = end.timestamp_micros() - start.timestamp_micros();
+        let _ = History::add(self.cid, &redact::line(&line), db, result.is_ok(), duration);
+        // the command is shown in the debug log like the commands of the requests
+        let mut log_args: Vec<String> = args
+            .iter()
+            .map(|a| match String::from_utf8(a.clone()) {
+                Ok(s) => s,
+                Err(_) => utils::binary_to_redis_str(a),
+            })
+            .collect();
+        redact::args(&mut log_args);
+        let mut log = Command {
+            id: utils::random_str(32),
+            connection_id: Some(self.cid),
+            cmd: log_args.join(" "),
+            response: CValue::Nil,
+            success: result.is_ok(),
+            host: self.conn.get_host(),
+            created_at: end.format("%Y-%m-%d %H:%M:%S").to_string(),
+            duration,
+        };
+        match result {
+            Ok(Some(value)) => {
+                log.response = value.clone();
+                redact::response(&log_args, &mut log.response);
+                self.emit(true, value);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                log.response = CValue::Str(e.to_string());
+                self.emit_error(e.to_string());
+            }
+        }
+        manager.log(log);
+    }
+
+    async fn normal(&self, cmd: &redis::Cmd) -> Result<CValue, CusError> {
+        let mut c = self.conn.get_conn(None).await?;
+        let value = cmd
+            .query_async::<RedisValue>(&mut c)
+            .await
+            .map_err(redis_err)?;
+        Ok(CValue::build(value))
+    }
+
+    // wait for the interrupt, the other commands are rejected while streaming
+    async fn wait_interrupt(&self, rx: &mut mpsc::UnboundedReceiver<String>) {
+        while let Some(payload) = rx.recv().await {
+            match parse_input(&payload) {
+                Some(Input::Interrupt { interrupt: true }) => return,
+                _ => self.emit_error("the command is running, interrupt it first".to_string()),
+            }
+        }
+    }
+
+    async fn blocking(&self, cmd: &redis::Cmd) -> Result<CValue, CusError> {
+        let conn = self.dedicated().await?;
+        let mut c = conn.get_conn(Some(self.db)).await?;
+        let value = cmd
+            .query_async::<RedisValue>(&mut c)
+            .await
+            .map_err(redis_err)?;
+        Ok(CValue::build(value))
+    }
+
+    async fn subscribe(&self, name: &str, channels: &[Vec<u8>]) -> Result<(), CusError> {
+        let connection = self.dedicated_resp2().await?;
+        let mut pubsub = connection.get_pubsub().await?;
+        for (i, channel) in channels.iter().enumerate() {
+            if name == "psubscribe" {
+                pubsub.psubscribe(channel.as_slice()).await?;
+            } else {
+                pubsub.subscribe(channel.as_slice()).await?;
+            }
+            self.emit(
+                true,
+                CValue::build(RedisValue::Array(vec![
+                    bulk(name.as_bytes()),
+                    bulk(channel),
+                    RedisValue::Int(i as i64 + 1),
+                ])),
+            );
+        }
+        self.emit(true, CValue::Str(READING.to_string()));
+        let mut messages = pubsub.on_message();
+        while let Some(msg) = messages.next().await {
+            let mut items = vec![];
+            if msg.from_pattern() {
+                items.push(bulk(b"pmessage"));
+                items.push(bulk(&msg.get_pattern::<Vec<u8>>()?));
+            } else {
+                items.push(bulk(b"message"));
+            }
+            items.push(bulk(msg.get_channel_name().as_bytes()));
+            items.push(bulk(msg.get_payload_bytes()));
+            self.emit(true, CValue::build(RedisValue::Array(items)));
+        }
+        drop(connection);
+        Ok(())
+    }
+
+    // sharded channels need RESP3, the owner of the slot is connected in cluster
+    async fn ssubscribe(&self, channels: &[Vec<u8>]) -> Result<(), CusError> {
+        let Some(first) = channels.first() else {
+            return Err(CusError::build("invalid args"));
+        };
+        let mut params = if self.nodes.is_empty() {
+            self.params.clone()
         } else {
-            resp_item.success = false;
-            resp_item.data = CValue::Str("invalid args".to_string());
+            let slot = get_slot(first);
+            self.nodes
+                .iter()
+                .find(|n| n.is_master() && n.slots.iter().any(|r| r.start <= slot && slot <= r.end))
+                .ok_or_else(|| CusError::App(format!("No node serves the slot {}", slot)))?
+                .get_params()
+        };
+        params.redis_params.protocol = redis::ProtocolVersion::RESP3;
+        params.response_timeout = None;
+        let conn = ConnectionWrapper::build(params).await?;
+        let mut push = conn.subscribe_push()?;
+        let channels: Vec<String> = channels
+            .iter()
+            .map(|c| String::from_utf8_lossy(c).to_string())
+            .collect();
+        conn.send_pubsub("ssubscribe", &channels).await?;
+        let mut reading = false;
+        loop {
+            match push.recv().await {
+                Ok(info) => {
+                    let kind = match info.kind {
+                        PushKind::Other(s) => s,
+                        kind => format!("{:?}", kind).to_lowercase(),
+                    };
+                    let is_message = kind == "smessage";
+                    let mut items = vec![bulk(kind.as_bytes())];
+                    items.extend(info.data);
+                    self.emit(true, CValue::build(RedisValue::Array(items)));
+                    if !is_message && !reading {
+                        reading = true;
+                        self.emit(true, CValue::Str(READING.to_string()));
+                    }
+                }
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
         }
-        resp_item
+        Ok(())
     }
 
-    if manager.get_is_cluster(cid).await {
-        let cell_conn = RefCell::new(manager.get_sync_cluster_conn(cid).await?);
-        let event_id = window.listen(inner_send_event_name.as_str(), move |event: Event| {
-            let mut resp_item = cmd_handle(event.payload(), |cmd| {
-                let mut conn = cell_conn.borrow_mut();
-                cmd.query(&mut conn)
-            });
-            resp_item.event = inner_receive_event_name.clone();
-            window_copy
-                .emit(inner_receive_event_name.as_str(), &resp_item)
-                .unwrap();
-        });
-        event.add(inner_send_event_name, event_id).await;
+    async fn monitor(&self) -> Result<(), CusError> {
+        let connection = self.dedicated_resp2().await?;
+        let mut monitor = connection.get_monitor().await?;
+        monitor.monitor().await?;
+        self.emit(true, CValue::Str("OK".to_string()));
+        let mut stream = monitor.into_on_message();
+        while let Some(msg) = stream.next().await {
+            self.emit(true, CValue::Str(String::from_redis_value(&msg)?));
+        }
+        drop(connection);
+        Ok(())
+    }
+
+    // read the streams again and again, the ids of XREAD are moved to the last entries
+    async fn xread(&self, name: &str, mut args: Vec<Vec<u8>>) -> Result<(), CusError> {
+        let streams = args
+            .iter()
+            .position(|a| a.eq_ignore_ascii_case(b"streams"))
+            .ok_or_else(|| CusError::build("syntax error"))?
+            + 1;
+        let count = (args.len() - streams) / 2;
+        let conn = self.dedicated().await?;
+        let mut c = conn.get_conn(Some(self.db)).await?;
+        loop {
+            let mut cmd = redis::cmd(name);
+            for arg in &args[1..] {
+                cmd.arg(arg.as_slice());
+            }
+            let value = cmd.query_async::<RedisValue>(&mut c).await?;
+            // BLOCK timeout
+            if value == RedisValue::Nil {
+                continue;
+            }
+            if name == "xread" {
+                for (key, entries) in stream_entries(&value) {
+                    let last = match entries {
+                        RedisValue::Array(items) => items.last(),
+                        _ => None,
+                    };
+                    let id = match last {
+                        Some(RedisValue::Array(entry)) => {
+                            entry.first().map(Vec::<u8>::from_redis_value)
+                        }
+                        _ => None,
+                    };
+                    let index = args[streams..streams + count]
+                        .iter()
+                        .position(|k| *k == key);
+                    if let (Some(Ok(id)), Some(index)) = (id, index) {
+                        args[streams + count + index] = id;
+                    }
+                }
+            }
+            self.emit(true, CValue::build(value));
+        }
+    }
+}
+
+fn parse_input(payload: &str) -> Option<Input> {
+    serde_json::from_str::<EventResp<Input>>(payload)
+        .ok()
+        .map(|r| r.data)
+}
+
+// the error reply is shown like redis-cli, without the kind
+fn redis_err(e: redis::RedisError) -> CusError {
+    CusError::App(e.detail().map(String::from).unwrap_or(e.to_string()))
+}
+
+fn bulk(v: &[u8]) -> RedisValue {
+    RedisValue::BulkString(v.to_vec())
+}
+
+// the reply of XREAD, the array of RESP2 or the map of RESP3
+fn stream_entries(value: &RedisValue) -> Vec<(Vec<u8>, &RedisValue)> {
+    match value {
+        RedisValue::Map(items) => items
+            .iter()
+            .filter_map(|(k, v)| Some((Vec::<u8>::from_redis_value(k).ok()?, v)))
+            .collect(),
+        RedisValue::Array(items) => items
+            .iter()
+            .filter_map(|item| match item {
+                RedisValue::Array(kv) if kv.len() == 2 => {
+                    Some((Vec::<u8>::from_redis_value(&kv[0]).ok()?, &kv[1]))
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+// every terminal runs its commands in order on a task with its own connection,
+// so SELECT only affects the session and a slow command never blocks the event thread
+// the streaming and blocking commands use dedicated connections until interrupted
+pub async fn open(
+    cid: u32,
+    window: Window,
+    manager: tauri::State<'_, Manager>,
+    event: tauri::State<'_, EventManager>,
+) -> Result<OpenArgs, CusError> {
+    let receive_event_name = utils::random_str(32);
+    let send_event_name = utils::random_str(32);
+
+    let wrapper = manager.get(cid).await?;
+    let nodes = if wrapper.is_cluster() {
+        manager.get_nodes_with(&wrapper).await?
     } else {
-        let cell_conn = RefCell::new(manager.get_sync_conn(cid).await?);
-        let event_handle = window.listen(inner_send_event_name.as_str(), move |event| {
-            let mut resp_item = cmd_handle(event.payload(), |cmd| {
-                let mut conn = cell_conn.borrow_mut();
-                cmd.query(&mut conn)
-            });
-            resp_item.event = inner_receive_event_name.clone();
-            window_copy
-                .emit(inner_receive_event_name.as_str(), &resp_item)
-                .unwrap();
-        });
-        event.add(inner_send_event_name, event_handle).await;
+        vec![]
+    };
+    let mut params = wrapper.model.origin.clone();
+    params.read_from_replica = false;
+    params.replica_params = None;
+    // the session starts on the default db of the connection, cluster only has db 0
+    let db = if wrapper.is_cluster() {
+        0
+    } else {
+        ConnectionModel::first(cid)?.db.unwrap_or_default()
+    };
+    let conn = ConnectionWrapper::build(params.clone()).await?;
+    if db != 0 {
+        let mut c = conn.get_conn(None).await?;
+        redis::cmd("select")
+            .arg(db)
+            .query_async::<()>(&mut c)
+            .await
+            .map_err(redis_err)?;
     }
+    let mut session = Session {
+        cid,
+        conn,
+        params,
+        nodes,
+        db,
+        window: window.clone(),
+        event: receive_event_name.clone(),
+        pending: None,
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    // the sender is dropped with the listener, then the task ends and the connection is closed
+    let event_id = window.listen(send_event_name.as_str(), move |event: Event| {
+        let _ = tx.send(event.payload().to_string());
+    });
+    event.add(send_event_name.clone(), event_id).await;
+
+    tokio::spawn(async move {
+        while let Some(payload) = rx.recv().await {
+            session.handle(&payload, &mut rx).await;
+        }
+    });
 
-    // event.add(id, conn);
     Ok(OpenArgs {
         send: send_event_name,
         receive: receive_event_name,
@@ -109,3 +534,211 @@ pub async fn cancel(
     }
     Ok(())
 }
+
+// the key completion never scans the whole keyspace
+const MAX_KEYS: usize = 20;
+const MAX_SCAN_ROUNDS: usize = 5;
+
+#[derive(Deserialize)]
+struct CompleteArgs {
+    line: String,
+    db: Option<u8>,
+}
+
+#[derive(Serialize)]
+struct CompletionItem {
+    label: String,
+    // command, subcommand, token or key
+    kind: &'static str,
+}
+
+#[derive(Serialize, Default)]
+pub struct Completion {
+    items: Vec<CompletionItem>,
+    // such as SET key value [NX | XX] [GET] ...
+    signature: Option<String>,
+    summary: Option<String>,
+}
+
+// the completions of the partially typed line and the help of the command
+pub async fn complete(
+    payload: String,
+    cid: u32,
+    manager: tauri::State<'_, Manager>,
+) -> Result<Completion, CusError> {
+    let args: CompleteArgs = serde_json::from_str(&payload)?;
+    let docs = manager.get_docs(cid).await?;
+    let mut tokens: Vec<String> = match utils::split_args(&args.line) {
+        Some(v) => v
+            .iter()
+            .map(|t| String::from_utf8_lossy(t).to_string())
+            .collect(),
+        // the quote is not closed yet
+        None => args.line.split_whitespace().map(String::from).collect(),
+    };
+    // a trailing space starts a new argument
+    let partial = if args.line.is_empty() || args.line.ends_with(char::is_whitespace) {
+        String::new()
+    } else {
+        tokens.pop().unwrap_or_default()
+    };
+    let upper = partial.to_uppercase();
+    let mut completion = Completion::default();
+
+    let Some(first) = tokens.first() else {
+        completion.items = docs
+            .commands
+            .values()
+            .map(|c| c.display_name())
+            .filter(|name| name.starts_with(&upper))
+            .map(|label| CompletionItem {
+                label,
+                kind: "command",
+            })
+            .collect();
+        return Ok(completion);
+    };
+    let Some(mut doc) = docs.get(first) else {
+        return Ok(completion);
+    };
+    let mut used = 1;
+    if !doc.subcommands.is_empty() {
+        match tokens.get(1) {
+            Some(sub) => {
+                if let Some(v) =
+                    doc.subcommands
+                        .get(&format!("{}|{}", doc.name, sub.to_lowercase()))
+                {
+                    doc = v;
+                    used = 2;
+                }
+            }
+            None => {
+                completion.items = doc
+                    .subcommands
+                    .values()
+                    .filter_map(|c| c.display_name().split_once(' ').map(|(_, s)| s.to_string()))
+                    .filter(|name| name.starts_with(&upper))
+                    .map(|label| CompletionItem {
+                        label,
+                        kind: "subcommand",
+                    })
+                    .collect();
+            }
+        }
+    }
+    completion.signature = Some(doc.signature());
+    completion.summary = Some(doc.summary.clone()).filter(|s| !s.is_empty());
+    if used == 1 && !doc.subcommands.is_empty() {
+        return Ok(completion);
+    }
+
+    let typed: HashSet<String> = tokens[used..].iter().map(|t| t.to_uppercase()).collect();
+    let mut seen = HashSet::new();
+    for token in doc.tokens() {
+        let name = token.to_uppercase();
+        if name.starts_with(&upper) && !typed.contains(&name) && seen.insert(name.clone()) {
+            completion.items.push(CompletionItem {
+                label: name,
+                kind: "token",
+            });
+        }
+    }
+    if doc.has_key() {
+        for key in scan_keys(&manager, cid, args.db, &partial).await? {
+            completion.items.push(CompletionItem {
+                label: key,
+                kind: "key",
+            });
+        }
+    }
+    Ok(completion)
+}
+
+// the keys start with the prefix, bounded by rounds and count
+async fn scan_keys(
+    manager: &Manager,
+    cid: u32,
+    db: Option<u8>,
+    prefix: &str,
+) -> Result<Vec<String>, CusError> {
+    let mut pattern = String::new();
+    for c in prefix.chars() {
+        if "*?[]\\".contains(c) {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('*');
+    // the cursor of cluster is only valid on the node scanned
+    let rounds = if manager.get_is_cluster(cid).await {
+        1
+    } else {
+        MAX_SCAN_ROUNDS
+    };
+    let mut keys = vec![];
+    let mut cursor = String::from("0");
+    for _ in 0..rounds {
+        let mut cmd = redis::cmd("SCAN");
+        cmd.arg(&cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(100);
+        let (next, batch): (String, Vec<Vec<u8>>) = manager.execute(cid, &mut cmd, db).await?;
+        for key in batch {
+            let key = match String::from_utf8(key) {
+                Ok(s) => s,
+                Err(e) => utils::binary_to_redis_str(&e.into_bytes()),
+            };
+            keys.push(key);
+            if keys.len() >= MAX_KEYS {
+                return Ok(keys);
+            }
+        }
+        if next == "0" {
+            break;
+        }
+        cursor = next;
+    }
+    Ok(keys)
+}
+
+#[derive(Deserialize)]
+struct HistoryArgs {
+    #[serde(default)]
+    keyword: String,
+    #[serde(default)]
+    offset: i64,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+pub async fn history(payload: String, cid: u32) -> Result<Vec<History>, CusError> {
+    let args: HistoryArgs = serde_json::from_str(&payload)?;
+    History::search(cid, &args.keyword, args.offset, args.limit)
+}
+
+#[derive(Deserialize)]
+struct StepArgs {
+    // the id of the current history, none is the end
+    from: Option<i64>,
+    #[serde(default)]
+    older: bool,
+    #[serde(default)]
+    prefix: String,
+}
+
+// the previous or next command, like the arrow keys
+pub async fn history_step(payload: String, cid: u32) -> Result<Option<History>, CusError> {
+    let args: StepArgs = serde_json::from_str(&payload)?;
+    History::step(cid, args.from, args.older, &args.prefix)
+}
+
+pub async fn history_clear(cid: u32) -> Result<(), CusError> {
+    History::clear(cid)
+}
@@ -0,0 +1,225 @@
+use rusqlite::{self, params, params_from_iter, Row};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
+
+use crate::{err::CusError, model::Command, sqlite};
+
+// the table is a ring buffer, the oldest logs are deleted
+const MAX_LOGS: i64 = 10000;
+// only a preview of the response is kept, the log is not a dump of the data
+const MAX_RESPONSE: usize = 1024;
+// the saved switch of the persistence, changed by debug/persist/set
+const PERSIST_FILE: &str = "command_log";
+
+// the commands are only written to sqlite if enabled by the user
+static PERSIST: AtomicBool = AtomicBool::new(false);
+
+fn persist_path() -> String {
+    format!("{}/{}", sqlite::get_data_dir(), PERSIST_FILE)
+}
+
+// a command executed by the manager
+#[derive(Debug, Serialize, Clone)]
+pub struct CommandLog {
+    pub id: i64,
+    pub connection_id: Option<u32>,
+    pub host: String,
+    pub cmd: String,
+    // the json of the response, or the preview of the large one
+    pub response: serde_json::Value,
+    pub success: bool,
+    // microseconds
+    pub duration: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct CommandLogFilter {
+    pub connection_id: Option<u32>,
+    pub host: Option<String>,
+    // matched anywhere in the command, case insensitive
+    pub command: Option<String>,
+    // microseconds
+    pub min_duration: Option<i64>,
+    #[serde(default)]
+    pub errors_only: bool,
+    #[serde(default)]
+    pub offset: i64,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+impl CommandLog {
+    pub fn build(r: &Row) -> CommandLog {
+        let response: String = r.get(4).unwrap_or_default();
+        CommandLog {
+            id: r.get(0).unwrap(),
+            connection_id: r.get(1).unwrap_or_default(),
+            host: r.get(2).unwrap_or_default(),
+            cmd: r.get(3).unwrap_or_default(),
+            response: serde_json::from_str(&response)
+                .unwrap_or(serde_json::Value::String(response)),
+            success: r.get(5).unwrap_or_default(),
+            duration: r.get(6).unwrap_or_default(),
+            created_at: r.get(7).unwrap_or_default(),
+        }
+    }
+
+    // the commands are written by a thread, so the sqlite io never blocks the request
+    pub fn start(rx: broadcast::Receiver<Command>) {
+        let enabled = fs::read_to_string(persist_path()).is_ok_and(|s| s.trim() == "1");
+        PERSIST.store(enabled, Ordering::Relaxed);
+        std::thread::spawn(move || {
+            if let Err(e) = write(rx) {
+                tracing::error!(error = %e, "command log stopped");
+            }
+        });
+    }
+
+    // the latest logs first
+    pub fn query(filter: &CommandLogFilter) -> Result<Vec<CommandLog>, CusError> {
+        let conn = sqlite::get_client()?;
+        let mut conditions: Vec<&str> = vec![];
+        let mut values: Vec<rusqlite::types::Value> = vec![];
+        if let Some(id) = filter.connection_id {
+            conditions.push("connection_id = ?");
+            values.push(id.into());
+        }
+        if let Some(host) = &filter.host {
+            conditions.push("host = ?");
+            values.push(host.clone().into());
+        }
+        if let Some(command) = &filter.command {
+            conditions.push("instr(lower(cmd), lower(?)) > 0");
+            values.push(command.clone().into());
+        }
+        if let Some(duration) = filter.min_duration {
+            conditions.push("duration >= ?");
+            values.push(duration.into());
+        }
+        if filter.errors_only {
+            conditions.push("success = 0");
+        }
+        let mut sql = String::from(
+            "select
+            id,
+            connection_id,
+            host,
+            cmd,
+            response,
+            success,
+            duration,
+            created_at
+            from command_logs",
+        );
+        if !conditions.is_empty() {
+            sql.push_str(" where ");
+            sql.push_str(&conditions.join(" and "));
+        }
+        sql.push_str(" order by id desc limit ? offset ?");
+        values.push(filter.limit.into());
+        values.push(filter.offset.into());
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), |r| Ok(Self::build(r)))?;
+        let mut result = vec![];
+        for x in rows {
+            result.push(x?);
+        }
+        Ok(result)
+    }
+
+    pub fn is_persisted() -> bool {
+        PERSIST.load(Ordering::Relaxed)
+    }
+
+    // it is kept for the next start
+    pub fn set_persisted(enabled: bool) -> Result<(), CusError> {
+        fs::write(persist_path(), if enabled { "1" } else { "0" })?;
+        PERSIST.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn clear() -> Result<(), CusError> {
+        let conn = sqlite::get_client()?;
+        conn.execute("delete from command_logs", ())?;
+        Ok(())
+    }
+}
+
+// write the commands until the manager dropped
+fn write(mut rx: broadcast::Receiver<Command>) -> Result<(), CusError> {
+    let mut conn = sqlite::get_client()?;
+    loop {
+        let first = match rx.blocking_recv() {
+            Ok(cmd) => cmd,
+            // the skipped commands are lost
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+        // the commands arrived together are written in one transaction
+        let mut batch = vec![first];
+        loop {
+            match rx.try_recv() {
+                Ok(cmd) => batch.push(cmd),
+                Err(TryRecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+        if !PERSIST.load(Ordering::Relaxed) {
+            continue;
+        }
+        // a failed batch is lost, the next ones are still written
+        let count = batch.len();
+        if let Err(e) = write_batch(&mut conn, batch) {
+            tracing::error!(count, error = %e, "command log batch failed");
+        }
+    }
+    Ok(())
+}
+
+fn write_batch(conn: &mut rusqlite::Connection, batch: Vec<Command>) -> Result<(), CusError> {
+    let tx = conn.transaction()?;
+    for cmd in batch {
+        let mut response = serde_json::to_string(&cmd.response)?;
+        if response.len() > MAX_RESPONSE {
+            let mut end = MAX_RESPONSE;
+            while !response.is_char_boundary(end) {
+                end -= 1;
+            }
+            response.truncate(end);
+            response.push_str("...");
+        }
+        tx.execute(
+            "insert into command_logs(
+                connection_id,
+                host,
+                cmd,
+                response,
+                success,
+                duration,
+                created_at
+                ) values(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params!(
+                cmd.connection_id,
+                cmd.host,
+                cmd.cmd,
+                response,
+                cmd.success,
+                cmd.duration,
+                cmd.created_at
+            ),
+        )?;
+    }
+    tx.execute(
+        "delete from command_logs where id <= (select max(id) from command_logs) - ?1",
+        [MAX_LOGS],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
@@ -1,6 +1,7 @@
-use crate::{connection, err::CusError, sqlite, ssh};
+use crate::{connection, err::CusError, proxy, sqlite, ssh};
 use rusqlite::{self, params, Row};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Connection {
@@ -19,13 +20,105 @@ pub struct Connection {
     pub ssh_private_key: Option<String>,
     pub ssh_timeout: Option<u32>,
     pub ssh_passphrase: Option<String>,
+    #[serde(default)]
+    pub read_from_replica: bool,
+    pub replica_host: Option<String>,
+    pub replica_port: Option<u16>,
+    // seconds
+    pub connect_timeout: Option<u32>,
+    pub response_timeout: Option<u32>,
+    #[serde(default)]
+    pub ssh_agent: bool,
+    // the bastions after the ssh host, in order
+    #[serde(default)]
+    pub ssh_jumps: Vec<ssh::SshHost>,
+    // socks5 or http
+    pub proxy_type: Option<String>,
+    pub proxy_host: Option<String>,
+    pub proxy_port: Option<u16>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    // the path of unix domain socket, host and port are ignored if set
+    pub socket_path: Option<String>,
+    // 2 or 3, RESP2 if not set
+    pub protocol: Option<u8>,
+    #[serde(default)]
+    pub tls: bool,
+    // the db selected when opened
+    pub db: Option<u8>,
+    // connect to the master of the name through the sentinel of host/port
+    pub sentinel_master: Option<String>,
+    // the auth of the sentinel, the sentinel may require a different password from the master
+    pub sentinel_username: Option<String>,
+    pub sentinel_password: Option<String>,
+    // the folder in the connection list
+    pub group: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub color: Option<String>,
+    // development, staging, production...
+    // writes to production connection must be confirmed
+    pub environment: Option<String>,
+    // the position in the list, ascending
+    #[serde(default)]
+    pub sort: i64,
 }
 
+// the environment that writes must be confirmed
+const PRODUCTION: &str = "production";
+
+const FIELDS: &str = "id,
+    name,
+    host,
+    port,
+    password,
+    username,
+    is_cluster,
+    readonly,
+    ssh_host,
+    ssh_port,
+    ssh_password,
+    ssh_username,
+    ssh_private_key,
+    ssh_timeout,
+    ssh_passphrase,
+    read_from_replica,
+    replica_host,
+    replica_port,
+    connect_timeout,
+    response_timeout,
+    ssh_agent,
+    ssh_jumps,
+    proxy_type,
+    proxy_host,
+    proxy_port,
+    proxy_username,
+    proxy_password,
+    socket_path,
+    protocol,
+    tls,
+    db,
+    sentinel_master,
+    group_name,
+    tags,
+    color,
+    environment,
+    sort,
+    sentinel_username,
+    sentinel_password";
+
 impl connection::Connectable for Connection {
     fn get_params(&self) -> connection::ConnectionParams {
+        let protocol = match self.protocol {
+            Some(3) => redis::ProtocolVersion::RESP3,
+            _ => redis::ProtocolVersion::RESP2,
+        };
         let redis_params = connection::ConnectedParam {
             tcp_host: self.host.clone(),
             tcp_port: self.port,
+            socket_path: self.socket_path.clone().filter(|p| !p.is_empty()),
+            protocol,
+            tls: self.tls,
             username: self.username.clone(),
             password: self.password.clone(),
         };
@@ -39,22 +132,69 @@ impl connection::Connectable for Connection {
             if let Some(u) = &self.ssh_username {
                 username = u.clone();
             }
-            let ssh_p = ssh::SshParams {
+            let mut hosts = vec![ssh::SshHost {
                 host: ssh_host.clone(),
                 port,
                 username,
                 password: self.ssh_password.clone(),
                 private_key: self.ssh_private_key.clone(),
                 passphrase: self.ssh_passphrase.clone(),
+                agent: self.ssh_agent,
+            }];
+            hosts.extend(self.ssh_jumps.iter().cloned());
+            let ssh_p = ssh::SshParams {
+                hosts,
                 target_host: self.host.clone(),
                 target_port: self.port,
+                timeout: self.ssh_timeout.map(|t| Duration::from_secs(t as u64)),
             };
             ssh_params = Some(ssh_p);
         }
+        let mut proxy_params = None;
+        if let (Some(proxy_type), Some(proxy_host)) = (&self.proxy_type, &self.proxy_host) {
+            let kind = match proxy_type.as_str() {
+                "http" => Some(proxy::ProxyKind::Http),
+                "socks5" => Some(proxy::ProxyKind::Socks5),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                proxy_params = Some(proxy::ProxyParams {
+                    kind,
+                    host: proxy_host.clone(),
+                    port: self.proxy_port.unwrap_or(1080),
+                    username: self.proxy_username.clone(),
+                    password: self.proxy_password.clone(),
+                    target_host: self.host.clone(),
+                    target_port: self.port,
+                    timeout: self.connect_timeout.map(|t| Duration::from_secs(t as u64)),
+                });
+            }
+        }
+        let mut replica_params = None;
+        if let Some(replica_host) = &self.replica_host {
+            replica_params = Some(connection::ConnectedParam {
+                tcp_host: replica_host.clone(),
+                tcp_port: self.replica_port.unwrap_or(self.port),
+                socket_path: None,
+                protocol,
+                tls: self.tls,
+                username: self.username.clone(),
+                password: self.password.clone(),
+            });
+        }
         connection::ConnectionParams {
             redis_params,
             ssh_params,
+            proxy_params,
             is_cluster: self.is_cluster,
+            read_from_replica: self.read_from_replica,
+            replica_params,
+            sentinel_master: self.sentinel_master.clone().filter(|m| !m.is_empty()),
+            sentinel_username: self.sentinel_username.clone().filter(|u| !u.is_empty()),
+            sentinel_password: self.sentinel_password.clone().filter(|p| !p.is_empty()),
+            connect_timeout: self.connect_timeout.map(|t| Duration::from_secs(t as u64)),
+            response_timeout: self.response_timeout.map(|t| Duration::from_secs(t as u64)),
+            confirm_write: self.is_production(),
         }
     }
 }
@@ -89,29 +229,44 @@ impl Connection {
             ssh_private_key: r.get(12).unwrap_or_default(),
             ssh_timeout: r.get(13).unwrap_or_default(),
             ssh_passphrase: r.get(14).unwrap_or_default(),
+            read_from_replica: r.get::<_, i64>(15).unwrap_or_default() > 0,
+            replica_host: r.get(16).unwrap_or_default(),
+            replica_port: r.get(17).unwrap_or_default(),
+            connect_timeout: r.get(18).unwrap_or_default(),
+            response_timeout: r.get(19).unwrap_or_default(),
+            ssh_agent: r.get::<_, i64>(20).unwrap_or_default() > 0,
+            ssh_jumps: r
+                .get::<_, Option<String>>(21)
+                .unwrap_or_default()
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            proxy_type: r.get(22).unwrap_or_default(),
+            proxy_host: r.get(23).unwrap_or_default(),
+            proxy_port: r.get(24).unwrap_or_default(),
+            proxy_username: r.get(25).unwrap_or_default(),
+            proxy_password: r.get(26).unwrap_or_default(),
+            socket_path: r.get(27).unwrap_or_default(),
+            protocol: r.get(28).unwrap_or_default(),
+            tls: r.get::<_, i64>(29).unwrap_or_default() > 0,
+            db: r.get(30).unwrap_or_default(),
+            sentinel_master: r.get(31).unwrap_or_default(),
+            group: r.get(32).unwrap_or_default(),
+            tags: r
+                .get::<_, Option<String>>(33)
+                .unwrap_or_default()
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            color: r.get(34).unwrap_or_default(),
+            environment: r.get(35).unwrap_or_default(),
+            sort: r.get(36).unwrap_or_default(),
+            sentinel_username: r.get(37).unwrap_or_default(),
+            sentinel_password: r.get(38).unwrap_or_default(),
         }
     }
 
     pub fn first(id: u32) -> Result<Connection, CusError> {
         let conn = sqlite::get_client()?;
-        let mut stmt = conn.prepare(
-            "select id, 
-            name,
-            host, 
-            port, 
-            password,
-            username,
-            is_cluster,
-            readonly,
-            ssh_host,
-            ssh_port,
-            ssh_password,
-            ssh_username,
-            ssh_private_key,
-            ssh_timeout,
-            ssh_passphrase
-            from connections where id= ?1",
-        )?;
+        let mut stmt = conn.prepare(&format!("select {} from connections where id= ?1", FIELDS))?;
         let c = stmt.query_row([id], |r| Ok(Self::build(r)))?;
         Ok(c)
     }
@@ -126,8 +281,21 @@ impl Connection {
         if self.readonly {
             readonly = 1;
         }
+        let mut read_from_replica = 0;
+        if self.read_from_replica {
+            read_from_replica = 1;
+        }
+        let mut ssh_agent = 0;
+        if self.ssh_agent {
+            ssh_agent = 1;
+        }
+        let ssh_jumps = serde_json::to_string(&self.ssh_jumps)?;
+        let tags = serde_json::to_string(&self.tags)?;
         if self.name.is_none() {
-            self.name = Some(format!("{}:{}", self.host, self.port))
+            match self.socket_path.as_ref().filter(|p| !p.is_empty()) {
+                Some(path) => self.name = Some(path.clone()),
+                None => self.name = Some(format!("{}:{}", self.host, self.port)),
+            }
         }
         if let Some(id) = self.id {
             conn.execute(
@@ -145,8 +313,32 @@ impl Connection {
                 ssh_username =?11,
                 ssh_private_key =?12,
                 ssh_timeout =?13,
-                ssh_passphrase =?14 
-                where id = ?15",
+                ssh_passphrase =?14,
+                read_from_replica =?15,
+                replica_host =?16,
+                replica_port =?17,
+                connect_timeout =?18,
+                response_timeout =?19,
+                ssh_agent =?20,
+                ssh_jumps =?21,
+                proxy_type =?22,
+                proxy_host =?23,
+                proxy_port =?24,
+                proxy_username =?25,
+                proxy_password =?26,
+                socket_path =?27,
+                protocol =?28,
+                tls =?29,
+                db =?30,
+                sentinel_master =?31,
+                group_name =?32,
+                tags =?33,
+                color =?34,
+                environment =?35,
+                sort =?36,
+                sentinel_username =?37,
+                sentinel_password =?38
+                where id = ?39",
                 params!(
                     self.name,
                     self.host,
@@ -162,6 +354,30 @@ impl Connection {
                     self.ssh_private_key,
                     self.ssh_timeout,
                     self.ssh_passphrase,
+                    read_from_replica,
+                    self.replica_host,
+                    self.replica_port,
+                    self.connect_timeout,
+                    self.response_timeout,
+                    ssh_agent,
+                    ssh_jumps,
+                    self.proxy_type,
+                    self.proxy_host,
+                    self.proxy_port,
+                    self.proxy_username,
+                    self.proxy_password,
+                    self.socket_path,
+                    self.protocol,
+                    self.tls,
+                    self.db,
+                    self.sentinel_master,
+                    self.group,
+                    tags,
+                    self.color,
+                    self.environment,
+                    self.sort,
+                    self.sentinel_username,
+                    self.sentinel_password,
                     id
                 ),
             )?;
@@ -181,8 +397,32 @@ impl Connection {
                     ssh_username,
                     ssh_private_key,
                     ssh_timeout,
-                    ssh_passphrase
-                    ) values(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                    ssh_passphrase,
+                    read_from_replica,
+                    replica_host,
+                    replica_port,
+                    connect_timeout,
+                    response_timeout,
+                    ssh_agent,
+                    ssh_jumps,
+                    proxy_type,
+                    proxy_host,
+                    proxy_port,
+                    proxy_username,
+                    proxy_password,
+                    socket_path,
+                    protocol,
+                    tls,
+                    db,
+                    sentinel_master,
+                    group_name,
+                    tags,
+                    color,
+                    environment,
+                    sort,
+                    sentinel_username,
+                    sentinel_password
+                    ) values(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37, ?38)",
                 params!(
                     &self.name,
                     &self.host,
@@ -198,6 +438,30 @@ impl Connection {
                     self.ssh_private_key,
                     self.ssh_timeout,
                     self.ssh_passphrase,
+                    read_from_replica,
+                    self.replica_host,
+                    self.replica_port,
+                    self.connect_timeout,
+                    self.response_timeout,
+                    ssh_agent,
+                    ssh_jumps,
+                    self.proxy_type,
+                    self.proxy_host,
+                    self.proxy_port,
+                    self.proxy_username,
+                    self.proxy_password,
+                    self.socket_path,
+                    self.protocol,
+                    self.tls,
+                    self.db,
+                    self.sentinel_master,
+                    self.group,
+                    tags,
+                    self.color,
+                    self.environment,
+                    self.sort,
+                    self.sentinel_username,
+                    self.sentinel_password,
                 ),
             )?;
             self.id = Some(conn.last_insert_rowid());
@@ -212,29 +476,66 @@ impl Connection {
             "delete from collections where connection_id = ?1",
             [self.id],
         )?;
+        conn.execute(
+            "delete from terminal_histories where connection_id = ?1",
+            [self.id],
+        )?;
+        conn.execute("delete from snippets where connection_id = ?1", [self.id])?;
         Ok(())
     }
 
-    pub fn all() -> Result<Vec<Connection>, CusError> {
+    pub fn is_production(&self) -> bool {
+        self.environment
+            .as_deref()
+            .is_some_and(|e| e.eq_ignore_ascii_case(PRODUCTION))
+    }
+
+    // the connections in the group and with the tag, ordered by sort
+    pub fn filter(group: Option<&str>, tag: Option<&str>) -> Result<Vec<Connection>, CusError> {
+        Ok(Self::all()?
+            .into_iter()
+            .filter(|c| group.is_none() || c.group.as_deref() == group)
+            .filter(|c| match tag {
+                Some(t) => c.tags.iter().any(|x| x == t),
+                None => true,
+            })
+            .collect())
+    }
+
+    // update the sort of the connections by the order of ids
+    pub fn reorder(ids: &[u32]) -> Result<(), CusError> {
+        let mut conn = sqlite::get_client()?;
+        let tx = conn.transaction()?;
+        for (i, id) in ids.iter().enumerate() {
+            tx.execute(
+                "update connections set sort = ?1 where id = ?2",
+                params!(i as i64, id),
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // the groups in use
+    pub fn groups() -> Result<Vec<String>, CusError> {
         let conn = sqlite::get_client()?;
-        let mut stmt_result = conn.prepare(
-            "select id,
-                name,
-                host, 
-                port,
-                password, 
-                username,
-                is_cluster,
-                readonly , 
-                 ssh_host,
-                ssh_port,
-                ssh_password,
-                ssh_username,
-                ssh_private_key,
-                ssh_timeout,
-                ssh_passphrase
-                from connections",
+        let mut stmt = conn.prepare(
+            "select distinct group_name from connections where group_name is not null and group_name != '' order by group_name",
         )?;
+        let rows = stmt.query_map([], |r| r.get::<_, String>(0))?;
+        let mut result = vec![];
+        for x in rows {
+            result.push(x?);
+        }
+        Ok(result)
+    }
+
+    pub fn all() -> Result<Vec<Connection>, CusError> {
+        let conn = sqlite::get_client()?;
+        let mut stmt_result = conn.prepare(&format!(
+            "select {} from connections order by sort, id",
+            FIELDS
+        ))?;
         let connections_result = stmt_result.query_map([], |row| Ok(Self::build(row)))?;
         let mut result: Vec<Connection> = vec![];
         for x in connections_result.into_iter() {
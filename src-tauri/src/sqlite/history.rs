@@ -0,0 +1,147 @@
+use rusqlite::{self, params, Row};
+use serde::Serialize;
+
+use crate::{err::CusError, sqlite};
+use chrono::prelude::*;
+
+// the commands kept per connection
+const MAX_HISTORIES: i64 = 1000;
+
+// a command executed in the terminal
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct History {
+    pub id: i64,
+    pub connection_id: u32,
+    pub command: String,
+    pub db: u8,
+    pub success: bool,
+    // microseconds
+    pub duration: i64,
+    pub created_at: String,
+}
+
+impl History {
+    pub fn build(r: &Row) -> History {
+        History {
+            id: r.get(0).unwrap(),
+            connection_id: r.get(1).unwrap(),
+            command: r.get(2).unwrap(),
+            db: r.get(3).unwrap_or_default(),
+            success: r.get(4).unwrap_or_default(),
+            duration: r.get(5).unwrap_or_default(),
+            created_at: r.get(6).unwrap(),
+        }
+    }
+
+    pub fn add(
+        connection_id: u32,
+        command: &str,
+        db: u8,
+        success: bool,
+        duration: i64,
+    ) -> Result<(), CusError> {
+        let conn = sqlite::get_client()?;
+        let time = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "insert into terminal_histories(
+                connection_id,
+                command,
+                db,
+                success,
+                duration,
+                created_at
+                ) values(?1, ?2, ?3, ?4, ?5, ?6)",
+            params!(connection_id, command, db, success, duration, time),
+        )?;
+        // the oldest commands of the connection are deleted
+        conn.execute(
+            "delete from terminal_histories where connection_id = ?1 and id <= (
+                select id from terminal_histories where connection_id = ?1
+                order by id desc limit 1 offset ?2
+            )",
+            params!(connection_id, MAX_HISTORIES),
+        )?;
+        Ok(())
+    }
+
+    // the latest commands first, the keyword is matched anywhere in the command
+    pub fn search(
+        connection_id: u32,
+        keyword: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<History>, CusError> {
+        let conn = sqlite::get_client()?;
+        let mut stmt = conn.prepare(
+            "select
+            id,
+            connection_id,
+            command,
+            db,
+            success,
+            duration,
+            created_at
+            from terminal_histories
+            where connection_id = ?1 and instr(lower(command), lower(?2)) > 0
+            order by id desc limit ?3 offset ?4",
+        )?;
+        let rows = stmt.query_map(params!(connection_id, keyword, limit, offset), |r| {
+            Ok(Self::build(r))
+        })?;
+        let mut result = vec![];
+        for x in rows {
+            result.push(x?);
+        }
+        Ok(result)
+    }
+
+    // the command before or after the id, like the arrow keys of a shell
+    // none id starts from the end, the prefix is the text already typed
+    pub fn step(
+        connection_id: u32,
+        from: Option<i64>,
+        older: bool,
+        prefix: &str,
+    ) -> Result<Option<History>, CusError> {
+        let conn = sqlite::get_client()?;
+        let sql = if older {
+            "select
+            id,
+            connection_id,
+            command,
+            db,
+            success,
+            duration,
+            created_at
+            from terminal_histories
+            where connection_id = ?1 and id < ?2 and substr(command, 1, length(?3)) = ?3
+            order by id desc limit 1"
+        } else {
+            "select
+            id,
+            connection_id,
+            command,
+            db,
+            success,
+            duration,
+            created_at
+            from terminal_histories
+            where connection_id = ?1 and id > ?2 and substr(command, 1, length(?3)) = ?3
+            order by id limit 1"
+        };
+        let mut stmt = conn.prepare(sql)?;
+        let from = from.unwrap_or(i64::MAX);
+        let mut rows =
+            stmt.query_map(params!(connection_id, from, prefix), |r| Ok(Self::build(r)))?;
+        Ok(rows.next().transpose()?)
+    }
+
+    pub fn clear(connection_id: u32) -> Result<(), CusError> {
+        let conn = sqlite::get_client()?;
+        conn.execute(
+            "delete from terminal_histories where connection_id = ?1",
+            [connection_id],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{err::CusError, sqlite::Connection};
+use base64::Engine;
+use percent_encoding::percent_decode_str;
+use serde::{de::DeserializeOwned, Deserialize};
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    // the export of this app
+    Json,
+    // Another Redis Desktop Manager
+    Ardm,
+    RedisInsight,
+    // one uri per line
+    Uri,
+}
+
+// the connection of Another Redis Desktop Manager
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ArdmConnection {
+    name: Option<String>,
+    host: String,
+    port: Option<u16>,
+    auth: Option<String>,
+    username: Option<String>,
+    cluster: bool,
+    #[serde(rename = "connectionReadOnly")]
+    readonly: bool,
+    #[serde(rename = "sshOptions")]
+    ssh: Option<ArdmSsh>,
+    #[serde(rename = "sslOptions")]
+    ssl: Option<serde_json::Value>,
+    #[serde(rename = "sentinelOptions")]
+    sentinel: Option<ArdmSentinel>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ArdmSsh {
+    host: String,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    privatekey: Option<String>,
+    passphrase: Option<String>,
+    timeout: Option<u32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct ArdmSentinel {
+    master_name: Option<String>,
+    node_password: Option<String>,
+}
+
+// the database of RedisInsight
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct InsightConnection {
+    name: Option<String>,
+    host: String,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    db: Option<u8>,
+    tls: bool,
+    connection_type: Option<String>,
+    sentinel_master: Option<InsightSentinel>,
+    ssh: bool,
+    ssh_options: Option<InsightSsh>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct InsightSentinel {
+    name: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct InsightSsh {
+    host: String,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    passphrase: Option<String>,
+}
+
+impl From<ArdmConnection> for Connection {
+    fn from(c: ArdmConnection) -> Self {
+        let mut connection = Connection {
+            name: c.name,
+            host: c.host,
+            port: c.port.unwrap_or(6379),
+            password: c.auth.filter(|s| !s.is_empty()),
+            username: c.username.filter(|s| !s.is_empty()),
+            is_cluster: c.cluster,
+            readonly: c.readonly,
+            tls: c.ssl.is_some(),
+            ..Default::default()
+        };
+        if let Some(ssh) = c.ssh.filter(|s| !s.host.is_empty()) {
+            connection.ssh_host = Some(ssh.host);
+            connection.ssh_port = ssh.port;
+            connection.ssh_username = ssh.username;
+            connection.ssh_password = ssh.password.filter(|s| !s.is_empty());
+            connection.ssh_private_key = ssh.privatekey.filter(|s| !s.is_empty());
+            connection.ssh_passphrase = ssh.passphrase.filter(|s| !s.is_empty());
+            connection.ssh_timeout = ssh.timeout;
+        }
+        // the password of ardm connection is the sentinel's, node password is the redis's
+        if let Some(sentinel) = c.sentinel {
+            if let Some(master) = sentinel.master_name.filter(|s| !s.is_empty()) {
+                connection.sentinel_master = Some(master);
+                connection.sentinel_password = connection.password.take();
+                connection.password = sentinel.node_password.filter(|s| !s.is_empty());
+            }
+        }
+        connection
+    }
+}
+
+impl From<InsightConnection> for Connection {
+    fn from(c: InsightConnection) -> Self {
+        let mut connection = Connection {
+            name: c.name,
+            host: c.host,
+            port: c.port.unwrap_or(6379),
+            password: c.password.filter(|s| !s.is_empty()),
+            username: c.username.filter(|s| !s.is_empty()),
+            db: c.db,
+            tls: c.tls,
+            ..Default::default()
+        };
+        match c.connection_type.as_deref() {
+            Some("CLUSTER") => connection.is_cluster = true,
+            Some("SENTINEL") => {
+                // the auth of the connection is the sentinel's, the master has its own
+                if let Some(master) = c.sentinel_master {
+                    connection.sentinel_master = master.name;
+                    connection.sentinel_username = connection.username.take();
+                    connection.sentinel_password = connection.password.take();
+                    connection.username = master.username.filter(|s| !s.is_empty());
+                    connection.password = master.password.filter(|s| !s.is_empty());
+                }
+            }
+            _ => {}
+        }
+        // the private key is exported as content, it must be set as a file path again
+        if let Some(ssh) = c.ssh_options.filter(|s| c.ssh && !s.host.is_empty()) {
+            connection.ssh_host = Some(ssh.host);
+            connection.ssh_port = ssh.port;
+            connection.ssh_username = ssh.username;
+            connection.ssh_password = ssh.password.filter(|s| !s.is_empty());
+            connection.ssh_passphrase = ssh.passphrase.filter(|s| !s.is_empty());
+        }
+        connection
+    }
+}
+
+// the exported file may be plain json, base64 json or base64 of uri encoded json
+fn parse_list<T: DeserializeOwned>(content: &str) -> Result<Vec<T>, CusError> {
+    let content = content.trim();
+    if let Ok(list) = serde_json::from_str(content) {
+        return Ok(list);
+    }
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(content)
+        .map_err(|_| CusError::build("Invalid import file"))?;
+    let decoded = String::from_utf8(decoded)?;
+    if let Ok(list) = serde_json::from_str(&decoded) {
+        return Ok(list);
+    }
+    let decoded = percent_decode_str(&decoded).decode_utf8_lossy();
+    Ok(serde_json::from_str(&decoded)?)
+}
+
+impl Connection {
+    // parse the connections from the exported content
+    // the connections are not saved
+    pub fn parse_import(format: ImportFormat, content: &str) -> Result<Vec<Connection>, CusError> {
+        let connections: Vec<Connection> = match format {
+            ImportFormat::Json => parse_list(content)?,
+            ImportFormat::Ardm => parse_list::<ArdmConnection>(content)?
+                .into_iter()
+                .map(Connection::from)
+                .collect(),
+            ImportFormat::RedisInsight => parse_list::<InsightConnection>(content)?
+                .into_iter()
+                .map(Connection::from)
+                .collect(),
+            ImportFormat::Uri => content
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(Connection::from_uri)
+                .collect::<Result<_, _>>()?,
+        };
+        Ok(connections
+            .into_iter()
+            .map(|mut c| {
+                c.id = None;
+                c
+            })
+            .collect())
+    }
+
+    // the copy without passwords and passphrases
+    pub fn without_secrets(&self) -> Connection {
+        let mut c = self.clone();
+        c.password = None;
+        c.ssh_password = None;
+        c.ssh_passphrase = None;
+        c.proxy_password = None;
+        c.sentinel_password = None;
+        for jump in c.ssh_jumps.iter_mut() {
+            jump.password = None;
+            jump.passphrase = None;
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_without_secrets() {
+        let c = Connection::from_uri(
+            "redis+sentinel://u:pw@s1:26379/mymaster?sentinel_username=su&sentinel_password=sp",
+        )
+        .unwrap();
+        let c = Connection {
+            ssh_password: Some("ssh".to_string()),
+            ssh_passphrase: Some("phrase".to_string()),
+            proxy_password: Some("proxy".to_string()),
+            ..c
+        };
+        let exported = serde_json::to_string(&c.without_secrets()).unwrap();
+        for secret in ["pw", "sp", "ssh", "phrase", "proxy"] {
+            assert!(
+                !exported.contains(&format!("\"{}\"", secret)),
+                "{}",
+                exported
+            );
+        }
+        let c = c.without_secrets();
+        assert_eq!(c.sentinel_password, None);
+        assert_eq!(c.sentinel_username.as_deref(), Some("su"));
+        assert_eq!(c.username.as_deref(), Some("u"));
+    }
+}
@@ -6,10 +6,21 @@ const DATA_DIR: &str = "redis";
 const DATA_NAME: &str = "data3.db";
 
 mod collection;
+mod command_log;
 mod connection;
+mod history;
+mod import;
+mod recording;
+mod snippet;
+mod uri;
 
 pub use collection::Collection;
+pub use command_log::{CommandLog, CommandLogFilter};
 pub use connection::Connection;
+pub use history::History;
+pub use import::ImportFormat;
+pub use recording::{RecordArgs, Recorder, Recording};
+pub use snippet::Snippet;
 
 pub fn get_client() -> Result<SqliteConnection, CusError> {
     let path = get_data_path();
@@ -36,11 +47,72 @@ pub fn init() {
             ssh_username  TEXT,
             ssh_private_key  TEXT,
             ssh_timeout  INTEGER,
-            ssh_passphrase  TEXT
+            ssh_passphrase  TEXT,
+            read_from_replica INTEGER NOT NULL DEFAULT 0,
+            replica_host TEXT,
+            replica_port INTEGER,
+            connect_timeout INTEGER,
+            response_timeout INTEGER,
+            ssh_agent INTEGER NOT NULL DEFAULT 0,
+            ssh_jumps TEXT,
+            proxy_type TEXT,
+            proxy_host TEXT,
+            proxy_port INTEGER,
+            proxy_username TEXT,
+            proxy_password TEXT,
+            socket_path TEXT,
+            protocol INTEGER,
+            tls INTEGER NOT NULL DEFAULT 0,
+            db INTEGER,
+            sentinel_master TEXT,
+            group_name TEXT,
+            tags TEXT,
+            color TEXT,
+            environment TEXT,
+            sort INTEGER NOT NULL DEFAULT 0,
+            sentinel_username TEXT,
+            sentinel_password TEXT
         )",
             (), // empty list of parameters.
         )
         .unwrap();
+    // columns added after the table created
+    add_column(
+        &client,
+        "connections",
+        "read_from_replica",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+    .unwrap();
+    add_column(&client, "connections", "replica_host", "TEXT").unwrap();
+    add_column(&client, "connections", "replica_port", "INTEGER").unwrap();
+    add_column(&client, "connections", "connect_timeout", "INTEGER").unwrap();
+    add_column(&client, "connections", "response_timeout", "INTEGER").unwrap();
+    add_column(
+        &client,
+        "connections",
+        "ssh_agent",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+    .unwrap();
+    add_column(&client, "connections", "ssh_jumps", "TEXT").unwrap();
+    add_column(&client, "connections", "proxy_type", "TEXT").unwrap();
+    add_column(&client, "connections", "proxy_host", "TEXT").unwrap();
+    add_column(&client, "connections", "proxy_port", "INTEGER").unwrap();
+    add_column(&client, "connections", "proxy_username", "TEXT").unwrap();
+    add_column(&client, "connections", "proxy_password", "TEXT").unwrap();
+    add_column(&client, "connections", "socket_path", "TEXT").unwrap();
+    add_column(&client, "connections", "protocol", "INTEGER").unwrap();
+    add_column(&client, "connections", "tls", "INTEGER NOT NULL DEFAULT 0").unwrap();
+    add_column(&client, "connections", "db", "INTEGER").unwrap();
+    add_column(&client, "connections", "sentinel_master", "TEXT").unwrap();
+    add_column(&client, "connections", "group_name", "TEXT").unwrap();
+    add_column(&client, "connections", "tags", "TEXT").unwrap();
+    add_column(&client, "connections", "color", "TEXT").unwrap();
+    add_column(&client, "connections", "environment", "TEXT").unwrap();
+    add_column(&client, "connections", "sort", "INTEGER NOT NULL DEFAULT 0").unwrap();
+    add_column(&client, "connections", "sentinel_username", "TEXT").unwrap();
+    add_column(&client, "connections", "sentinel_password", "TEXT").unwrap();
     client
         .execute(
             "CREATE TABLE IF NOT EXISTS collections (
@@ -55,9 +127,126 @@ pub fn init() {
             (), // empty list of parameters.
         )
         .unwrap();
+    client
+        .execute(
+            "CREATE TABLE IF NOT EXISTS recordings (
+        id    INTEGER PRIMARY KEY,
+        connection_id INTEGER NOT NULL,
+        name  TEXT NOT NULL,
+        types TEXT NOT NULL,
+        count INTEGER NOT NULL DEFAULT 0,
+        size INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL,
+        ended_at TEXT
+    )",
+            (),
+        )
+        .unwrap();
+    client
+        .execute(
+            "CREATE TABLE IF NOT EXISTS recording_messages (
+        id    INTEGER PRIMARY KEY,
+        recording_id INTEGER NOT NULL,
+        elapsed INTEGER NOT NULL,
+        channel TEXT,
+        pattern TEXT,
+        sharded INTEGER NOT NULL DEFAULT 0,
+        payload BLOB NOT NULL
+    )",
+            (),
+        )
+        .unwrap();
+    client
+        .execute(
+            "CREATE INDEX IF NOT EXISTS recording_messages_recording_id ON recording_messages (recording_id)",
+            (),
+        )
+        .unwrap();
+    client
+        .execute(
+            "CREATE TABLE IF NOT EXISTS terminal_histories (
+        id    INTEGER PRIMARY KEY,
+        connection_id INTEGER NOT NULL,
+        command TEXT NOT NULL,
+        db INTEGER NOT NULL DEFAULT 0,
+        success INTEGER NOT NULL DEFAULT 1,
+        duration INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL
+    )",
+            (),
+        )
+        .unwrap();
+    client
+        .execute(
+            "CREATE INDEX IF NOT EXISTS terminal_histories_connection_id ON terminal_histories (connection_id)",
+            (),
+        )
+        .unwrap();
+    client
+        .execute(
+            "CREATE TABLE IF NOT EXISTS command_logs (
+        id    INTEGER PRIMARY KEY,
+        connection_id INTEGER,
+        host TEXT NOT NULL,
+        cmd TEXT NOT NULL,
+        response TEXT,
+        success INTEGER NOT NULL DEFAULT 1,
+        duration INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL
+    )",
+            (),
+        )
+        .unwrap();
+    client
+        .execute(
+            "CREATE INDEX IF NOT EXISTS command_logs_connection_id ON command_logs (connection_id, id)",
+            (),
+        )
+        .unwrap();
+    client
+        .execute(
+            "CREATE TABLE IF NOT EXISTS snippets (
+        id    INTEGER PRIMARY KEY,
+        connection_id INTEGER,
+        name  TEXT NOT NULL,
+        template TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    )",
+            (),
+        )
+        .unwrap();
+}
+
+// add the column if the table is created by old version
+fn add_column(
+    client: &SqliteConnection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), CusError> {
+    let mut stmt = client.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt.query_map([], |r| r.get::<_, String>(1))?;
+    for name in columns {
+        if name? == column {
+            return Ok(());
+        }
+    }
+    client.execute(
+        &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+        (),
+    )?;
+    Ok(())
 }
 
 fn get_data_path() -> String {
+    let mut full_dir = get_data_dir();
+    full_dir.push_str("/");
+    full_dir.push_str(DATA_NAME);
+    full_dir
+}
+
+// the data dir of the app, created if not exists
+pub fn get_data_dir() -> String {
     if let Some(data_dir) = dirs_next::data_dir() {
         let mut full_dir: String = String::from(data_dir.to_str().unwrap());
         full_dir.push_str("/");
@@ -69,8 +258,6 @@ fn get_data_path() -> String {
             }
             _ => {}
         }
-        full_dir.push_str("/");
-        full_dir.push_str(DATA_NAME);
         return full_dir;
     } else {
         panic!("sqlite error: data dir not exists")
@@ -0,0 +1,244 @@
+use rusqlite::{self, params, Row};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::{err::CusError, sqlite};
+use chrono::prelude::*;
+
+// a captured pubsub or monitor session
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct Recording {
+    pub id: i64,
+    pub connection_id: u32,
+    pub name: String,
+    // pubsub or monitor
+    pub types: String,
+    pub count: i64,
+    // the total bytes of the payloads
+    pub size: i64,
+    pub created_at: String,
+    pub ended_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordMessage {
+    // microseconds since the recording started
+    pub elapsed: i64,
+    // none for monitor
+    pub channel: Option<String>,
+    pub pattern: Option<String>,
+    pub sharded: bool,
+    pub payload: Vec<u8>,
+}
+
+// the limits of a recording, it stops when any is reached
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RecordArgs {
+    pub name: Option<String>,
+    pub max_bytes: Option<i64>,
+    pub max_seconds: Option<u64>,
+}
+
+// the messages are written by a thread, so the sqlite io never blocks the runtime
+pub struct Recorder {
+    tx: mpsc::Sender<RecordMessage>,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn start(connection_id: u32, types: &str, args: RecordArgs) -> Result<Self, CusError> {
+        let conn = sqlite::get_client()?;
+        let time = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let name = args
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("{} {}", types, time));
+        conn.execute(
+            "insert into recordings(
+                connection_id,
+                name,
+                types,
+                count,
+                size,
+                created_at
+                ) values(?1, ?2, ?3, 0, 0, ?4)",
+            params!(connection_id, name, types, time),
+        )?;
+        let id = conn.last_insert_rowid();
+        let (tx, rx) = mpsc::channel::<RecordMessage>();
+        std::thread::spawn(move || {
+            if let Err(e) = write(id, rx, args) {
+                tracing::error!(id, error = %e, "recording failed");
+            }
+        });
+        Ok(Self {
+            tx,
+            started: Instant::now(),
+        })
+    }
+
+    // the message is dropped silently after the limits reached
+    pub fn record(
+        &self,
+        channel: Option<&str>,
+        pattern: Option<&str>,
+        sharded: bool,
+        payload: &[u8],
+    ) {
+        let _ = self.tx.send(RecordMessage {
+            elapsed: self.started.elapsed().as_micros() as i64,
+            channel: channel.map(String::from),
+            pattern: pattern.map(String::from),
+            sharded,
+            payload: payload.to_vec(),
+        });
+    }
+}
+
+// write the messages until the recorder dropped or the limits reached
+fn write(id: i64, rx: mpsc::Receiver<RecordMessage>, args: RecordArgs) -> Result<(), CusError> {
+    let mut conn = sqlite::get_client()?;
+    let max_elapsed = args
+        .max_seconds
+        .map(|s| Duration::from_secs(s).as_micros() as i64);
+    let mut count: i64 = 0;
+    let mut size: i64 = 0;
+    let mut reached = false;
+    while let Ok(first) = rx.recv() {
+        // the messages arrived together are written in one transaction
+        let mut batch = vec![first];
+        batch.extend(rx.try_iter());
+        let tx = conn.transaction()?;
+        for m in batch {
+            let exceeded = max_elapsed.is_some_and(|max| m.elapsed > max)
+                || args
+                    .max_bytes
+                    .is_some_and(|max| size + m.payload.len() as i64 > max);
+            if exceeded {
+                reached = true;
+                break;
+            }
+            tx.execute(
+                "insert into recording_messages(
+                    recording_id,
+                    elapsed,
+                    channel,
+                    pattern,
+                    sharded,
+                    payload
+                    ) values(?1, ?2, ?3, ?4, ?5, ?6)",
+                params!(id, m.elapsed, m.channel, m.pattern, m.sharded, m.payload),
+            )?;
+            count += 1;
+            size += m.payload.len() as i64;
+        }
+        tx.execute(
+            "update recordings set count = ?1, size = ?2 where id = ?3",
+            params!(count, size, id),
+        )?;
+        tx.commit()?;
+        if reached {
+            break;
+        }
+    }
+    let time = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    conn.execute(
+        "update recordings set ended_at = ?1 where id = ?2",
+        params!(time, id),
+    )?;
+    Ok(())
+}
+
+impl Recording {
+    pub fn build(r: &Row) -> Recording {
+        Recording {
+            id: r.get(0).unwrap(),
+            connection_id: r.get(1).unwrap(),
+            name: r.get(2).unwrap(),
+            types: r.get(3).unwrap(),
+            count: r.get(4).unwrap_or_default(),
+            size: r.get(5).unwrap_or_default(),
+            created_at: r.get(6).unwrap(),
+            ended_at: r.get(7).unwrap_or_default(),
+        }
+    }
+
+    pub fn first(id: i64) -> Result<Recording, CusError> {
+        let conn = sqlite::get_client()?;
+        let mut stmt = conn.prepare(
+            "select
+            id,
+            connection_id,
+            name,
+            types,
+            count,
+            size,
+            created_at,
+            ended_at
+            from recordings where id = ?1",
+        )?;
+        let r = stmt.query_row([id], |r| Ok(Self::build(r)))?;
+        Ok(r)
+    }
+
+    pub fn all() -> Result<Vec<Recording>, CusError> {
+        let conn = sqlite::get_client()?;
+        let mut stmt = conn.prepare(
+            "select
+            id,
+            connection_id,
+            name,
+            types,
+            count,
+            size,
+            created_at,
+            ended_at
+            from recordings order by id desc",
+        )?;
+        let rows = stmt.query_map([], |row| Ok(Self::build(row)))?;
+        let mut result: Vec<Recording> = vec![];
+        for x in rows {
+            result.push(x?);
+        }
+        Ok(result)
+    }
+
+    // the messages in recorded order
+    pub fn messages(&self, offset: i64, limit: i64) -> Result<Vec<RecordMessage>, CusError> {
+        let conn = sqlite::get_client()?;
+        let mut stmt = conn.prepare(
+            "select
+            elapsed,
+            channel,
+            pattern,
+            sharded,
+            payload
+            from recording_messages where recording_id = ?1 order by id limit ?2 offset ?3",
+        )?;
+        let rows = stmt.query_map(params!(self.id, limit, offset), |r| {
+            Ok(RecordMessage {
+                elapsed: r.get(0)?,
+                channel: r.get(1)?,
+                pattern: r.get(2)?,
+                sharded: r.get(3)?,
+                payload: r.get(4)?,
+            })
+        })?;
+        let mut result = vec![];
+        for x in rows {
+            result.push(x?);
+        }
+        Ok(result)
+    }
+
+    pub fn del(self) -> Result<(), CusError> {
+        let conn = sqlite::get_client()?;
+        conn.execute(
+            "delete from recording_messages where recording_id = ?1",
+            [self.id],
+        )?;
+        conn.execute("delete from recordings where id = ?1", [self.id])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use rusqlite::{self, params, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{err::CusError, sqlite, utils};
+use chrono::prelude::*;
+
+// a command template of the terminal, such as HGETALL user:{id}
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Snippet {
+    pub id: Option<i64>,
+    // none is shared by all connections
+    pub connection_id: Option<u32>,
+    pub name: String,
+    pub template: String,
+    pub created_at: Option<String>,
+}
+
+impl Snippet {
+    pub fn build(r: &Row) -> Snippet {
+        Snippet {
+            id: r.get(0).unwrap(),
+            connection_id: r.get(1).unwrap_or_default(),
+            name: r.get(2).unwrap(),
+            template: r.get(3).unwrap(),
+            created_at: r.get(4).unwrap(),
+        }
+    }
+
+    pub fn first(id: i64) -> Result<Snippet, CusError> {
+        let conn = sqlite::get_client()?;
+        let mut stmt = conn.prepare(
+            "select
+            id,
+            connection_id,
+            name,
+            template,
+            created_at
+            from snippets where id = ?1",
+        )?;
+        let r = stmt.query_row([id], |r| Ok(Self::build(r)))?;
+        Ok(r)
+    }
+
+    // the snippet run on the connection, the one of another connection is rejected
+    pub fn first_of(id: i64, connection_id: u32) -> Result<Snippet, CusError> {
+        let snippet = Self::first(id)?;
+        if snippet.connection_id.is_some_and(|c| c != connection_id) {
+            return Err(CusError::build("The snippet belongs to another connection"));
+        }
+        Ok(snippet)
+    }
+
+    // the snippets of the connection and the shared ones
+    pub fn all(connection_id: u32) -> Result<Vec<Snippet>, CusError> {
+        let conn = sqlite::get_client()?;
+        let mut stmt = conn.prepare(
+            "select
+            id,
+            connection_id,
+            name,
+            template,
+            created_at
+            from snippets where connection_id is null or connection_id = ?1 order by name",
+        )?;
+        let rows = stmt.query_map([connection_id], |r| Ok(Self::build(r)))?;
+        let mut result = vec![];
+        for x in rows {
+            result.push(x?);
+        }
+        Ok(result)
+    }
+
+    pub fn save(&mut self) -> Result<(), CusError> {
+        let conn = sqlite::get_client()?;
+        match self.id {
+            Some(id) => {
+                conn.execute(
+                    "update snippets set connection_id = ?1, name = ?2, template = ?3 where id = ?4",
+                    params!(self.connection_id, &self.name, &self.template, id),
+                )?;
+            }
+            None => {
+                let time = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+                conn.execute(
+                    "insert into snippets(
+                        connection_id,
+                        name,
+                        template,
+                        created_at
+                        ) values(?1, ?2, ?3, ?4)",
+                    params!(self.connection_id, &self.name, &self.template, &time),
+                )?;
+                self.id = Some(conn.last_insert_rowid());
+                self.created_at = Some(time);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn del(self) -> Result<(), CusError> {
+        let conn = sqlite::get_client()?;
+        conn.execute("delete from snippets where id = ?1", [self.id])?;
+        Ok(())
+    }
+
+    // the names of the placeholders in order
+    pub fn params(&self) -> Vec<String> {
+        let mut result: Vec<String> = vec![];
+        for (name, _, _) in placeholders(&self.template) {
+            if !result.contains(&name) {
+                result.push(name);
+            }
+        }
+        result
+    }
+
+    // replace the placeholders by the quoted values
+    // the placeholder without value is kept, so the hash tag such as {user} still works
+    pub fn expand(&self, values: &HashMap<String, String>) -> String {
+        let mut s = String::new();
+        let mut last = 0;
+        for (name, start, end) in placeholders(&self.template) {
+            if let Some(value) = values.get(&name) {
+                s.push_str(&self.template[last..start]);
+                s.push_str(&utils::quote_arg(value));
+                last = end;
+            }
+        }
+        s.push_str(&self.template[last..]);
+        s
+    }
+}
+
+// the {name} in the template, with the byte range
+fn placeholders(template: &str) -> Vec<(String, usize, usize)> {
+    let mut result = vec![];
+    let mut start = None;
+    for (i, c) in template.char_indices() {
+        match c {
+            '{' => start = Some(i),
+            '}' => {
+                if let Some(s) = start.take() {
+                    let name = &template[s + 1..i];
+                    let valid = name
+                        .chars()
+                        .next()
+                        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    if valid {
+                        result.push((name.to_string(), s, i + 1));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(template: &str) -> Snippet {
+        Snippet {
+            template: template.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn params() {
+        let cases = [
+            ("HGETALL user:{id}", vec!["id"]),
+            (
+                "SET {key} {value} EX {ttl} GET {key}",
+                vec!["key", "value", "ttl"],
+            ),
+            // not names, such as json and the empty braces
+            (r#"JSON.SET k $ '{"a":1}' {} {1x} {a-b}"#, vec![]),
+            ("GET {{nested}} {_a1}", vec!["nested", "_a1"]),
+            ("GET {unclosed", vec![]),
+        ];
+        for (template, expected) in cases {
+            assert_eq!(snippet(template).params(), expected, "{}", template);
+        }
+    }
+
+    #[test]
+    fn expand() {
+        let values = HashMap::from([
+            ("id".to_string(), "42".to_string()),
+            ("value".to_string(), "hello world".to_string()),
+            ("quote".to_string(), r#"say "hi""#.to_string()),
+            ("empty".to_string(), String::new()),
+        ]);
+        let cases = [
+            ("HGETALL user:{id}", "HGETALL user:42"),
+            ("SET k {value}", r#"SET k "hello world""#),
+            ("SET k {quote}", r#"SET k "say \"hi\"""#),
+            ("SET k {empty}", r#"SET k """#),
+            ("MGET {id} {id}", "MGET 42 42"),
+            // the placeholder without value is kept, such as the hash tag
+            ("GET {user}:{id}", "GET {user}:42"),
+            ("GET 用户:{id}", "GET 用户:42"),
+        ];
+        for (template, expected) in cases {
+            assert_eq!(snippet(template).expand(&values), expected, "{}", template);
+        }
+    }
+}
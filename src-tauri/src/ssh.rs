@@ -1,80 +1,279 @@
 use crate::err::CusError;
-use crate::utils;
-use ssh_jumper::{
-    model::{HostAddress, HostSocketParams, JumpHostAuthParams, SshForwarderEnd, SshTunnelParams},
-    SshJumper,
-};
-use std::borrow::Cow;
-use std::{net::SocketAddr, path::Path};
-use tokio::sync::oneshot::Receiver;
+use crate::proxy::ProxyParams;
+use crate::sqlite;
+use async_io::Async;
+use async_ssh2_lite::ssh2::{CheckResult, HashType, KnownHostFileKind, KnownHostKeyFormat};
+use async_ssh2_lite::{AsyncChannel, AsyncSession, SessionConfiguration};
+use futures::io::{copy, AsyncReadExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use ssh_jumper::model::SshForwarderEnd;
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot::{self, Receiver, Sender};
 
-#[derive(Debug, Clone)]
-pub struct SshParams {
+const KNOWN_HOSTS: &str = "known_hosts";
+
+// the host keys not in known_hosts, wait for user to trust
+static PENDING_KEYS: Lazy<Mutex<HashMap<String, PendingKey>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// a ssh server (bastion) on the way to redis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshHost {
     pub host: String,
     pub port: u16,
     pub username: String,
     pub password: Option<String>,
     pub private_key: Option<String>,
     pub passphrase: Option<String>,
+    // authenticate with the identities of ssh-agent
+    #[serde(default)]
+    pub agent: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SshParams {
+    // the bastions in order, the last one connects to the target
+    pub hosts: Vec<SshHost>,
     pub target_host: String,
     pub target_port: u16,
+    // the timeout of connecting and handshaking with the jump host
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingKey {
+    pub host: String,
+    pub port: u16,
+    pub key_type: String,
+    pub fingerprint: String,
+    #[serde(skip)]
+    key: Vec<u8>,
+    #[serde(skip)]
+    format: KnownHostKeyFormat,
 }
 
 pub trait SshProxy {
     fn store_addr(&mut self, addr: SocketAddr, rx: Receiver<SshForwarderEnd>);
     fn get_ssh_config(&self) -> Option<SshParams>;
+    fn get_proxy_config(&self) -> Option<ProxyParams>;
     fn close_tunnel(&mut self);
 }
 // create ssh proxy
 pub async fn create_tunnel<T: SshProxy>(t: &mut T) -> Result<Option<()>, CusError> {
     if let Some(config) = t.get_ssh_config() {
-        let mut jump_host = HostAddress::HostName(Cow::Borrowed(&config.host));
-        if let Ok(ip_addr) = utils::string_to_ip(&config.host) {
-            jump_host = HostAddress::IpAddr(ip_addr);
-        }
-        let mut auth: JumpHostAuthParams;
-        let mut password = String::new();
-        if let Some(p) = config.password {
-            password = p;
-        }
-        auth = JumpHostAuthParams::password(
-            Cow::from(config.username.as_str()),
-            Cow::from(password.as_str()),
-        );
-
-        let mut private_key = String::new();
-        if let Some(k) = config.private_key {
-            private_key = k
-        }
-        let mut passphrase_str = String::new();
-        let mut passphrase = None;
-        if let Some(p) = config.passphrase {
-            passphrase_str = p
-        }
-        if passphrase_str != "" {
-            passphrase = Some(Cow::from(passphrase_str.as_str()));
-        }
-        if private_key != "" {
-            auth = JumpHostAuthParams::key_pair(
-                Cow::from(config.username.as_str()),
-                Cow::from(Path::new(private_key.as_str())),
-                passphrase,
-            );
+        let (addr, rx) = match config.timeout {
+            Some(d) => tokio::time::timeout(d, open_tunnel(&config))
+                .await
+                .map_err(|_| CusError::ssh_timeout(d))??,
+            None => open_tunnel(&config).await?,
+        };
+        t.store_addr(addr, rx)
+    }
+    Ok(None)
+}
+
+// connect the bastions one by one, every bastion is reached through the forwarded port of the previous one
+async fn open_tunnel(
+    config: &SshParams,
+) -> Result<(SocketAddr, Receiver<SshForwarderEnd>), CusError> {
+    let (first, rest) = config
+        .hosts
+        .split_first()
+        .ok_or_else(|| CusError::build("SSH host is required"))?;
+    let addr = resolve(&first.host, first.port).await?;
+    let mut session = connect(first, addr).await?;
+    let mut upstream = vec![];
+    for hop in rest {
+        let (addr, rx) = forward(session, &hop.host, hop.port, upstream)?;
+        upstream = vec![rx];
+        session = connect(hop, addr).await?;
+    }
+    forward(session, &config.target_host, config.target_port, upstream)
+}
+
+async fn resolve(host: &str, port: u16) -> Result<SocketAddr, CusError> {
+    tokio::net::lookup_host((host, port))
+        .await?
+        .next()
+        .ok_or_else(|| CusError::App(format!("Can not resolve SSH host: {}", host)))
+}
+
+// handshake, verify the host key and authenticate
+async fn connect(hop: &SshHost, addr: SocketAddr) -> Result<AsyncSession<TcpStream>, CusError> {
+    let stream = Async::<TcpStream>::connect(addr).await?;
+    let mut config = SessionConfiguration::new();
+    config.set_compress(true);
+    let mut session = AsyncSession::new(stream, Some(config))?;
+    session.handshake().await?;
+    check_host_key(&session, &hop.host, hop.port)?;
+
+    if hop.agent {
+        session.userauth_agent_with_try_next(&hop.username).await?;
+    } else if let Some(key) = hop.private_key.as_ref().filter(|k| !k.is_empty()) {
+        let passphrase = hop.passphrase.as_deref().filter(|p| !p.is_empty());
+        session
+            .userauth_pubkey_file(&hop.username, None, Path::new(key), passphrase)
+            .await?;
+    } else {
+        let password = hop.password.clone().unwrap_or_default();
+        session.userauth_password(&hop.username, &password).await?;
+    }
+    if !session.authenticated() {
+        return Err(CusError::App(format!(
+            "SSH authentication failed: {}@{}",
+            hop.username, hop.host
+        )));
+    }
+    Ok(session)
+}
+
+// listen on a local port, forward every accepted connection to the host through the session
+// the forwarder stops when the receiver is closed, the upstream forwarders stop with it
+fn forward(
+    session: AsyncSession<TcpStream>,
+    host: &str,
+    port: u16,
+    upstream: Vec<Receiver<SshForwarderEnd>>,
+) -> Result<(SocketAddr, Receiver<SshForwarderEnd>), CusError> {
+    let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 0))?;
+    let addr = listener.get_ref().local_addr()?;
+    let (mut tx, rx) = oneshot::channel::<SshForwarderEnd>();
+    let session = Arc::new(session);
+    let host = host.to_string();
+    tokio::spawn(async move {
+        let _upstream = upstream;
+        loop {
+            let accepted = tokio::select! {
+                _ = tx.closed() => return,
+                r = listener.accept() => r,
+            };
+            match accepted {
+                Ok((stream, _)) => match session.channel_direct_tcpip(&host, port, None).await {
+                    Ok(channel) => {
+                        tokio::spawn(pipe(stream, channel));
+                    }
+                    Err(e) => return end(tx, SshForwarderEnd::ChannelReadErr(e)),
+                },
+                Err(e) => return end(tx, SshForwarderEnd::LocalConnectFail(e)),
+            }
         }
-        let mut target_host = HostAddress::HostName(Cow::Borrowed(&config.target_host));
-        if let Ok(ip_addr) = utils::string_to_ip(&config.target_host) {
-            target_host = HostAddress::IpAddr(ip_addr);
+    });
+    Ok((addr, rx))
+}
+
+fn end(tx: Sender<SshForwarderEnd>, reason: SshForwarderEnd) {
+    let _ = tx.send(reason);
+}
+
+// copy the data between the local stream and the channel until one side closed
+async fn pipe(stream: Async<TcpStream>, channel: AsyncChannel<TcpStream>) {
+    let (mut local_r, mut local_w) = stream.split();
+    let (mut channel_r, mut channel_w) = channel.split();
+    tokio::select! {
+        _ = copy(&mut local_r, &mut channel_w) => {},
+        _ = copy(&mut channel_r, &mut local_w) => {},
+    }
+}
+
+fn get_known_hosts_path() -> PathBuf {
+    Path::new(&sqlite::get_data_dir()).join(KNOWN_HOSTS)
+}
+
+fn host_entry(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+// check the host key with the known_hosts of the app and ~/.ssh/known_hosts
+// the unknown key is kept until the user trusts it
+fn check_host_key(
+    session: &AsyncSession<TcpStream>,
+    host: &str,
+    port: u16,
+) -> Result<(), CusError> {
+    let (key, key_type) = session
+        .host_key()
+        .ok_or_else(|| CusError::build("SSH host key not found"))?;
+    let mut known_hosts = session.known_hosts()?;
+    let mut files = vec![get_known_hosts_path()];
+    if let Some(home) = dirs_next::home_dir() {
+        files.push(home.join(".ssh").join(KNOWN_HOSTS));
+    }
+    for file in files.iter().filter(|f| f.exists()) {
+        let _ = known_hosts.read_file(file, KnownHostFileKind::OpenSSH);
+    }
+    let fingerprint = format!(
+        "SHA256:{}",
+        base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD_NO_PAD,
+            session.host_key_hash(HashType::Sha256).unwrap_or_default()
+        )
+    );
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(CusError::App(format!(
+            "SSH host key of {} has changed ({}), it may be a man-in-the-middle attack. Remove the old key from known_hosts if the change is expected",
+            host_entry(host, port),
+            fingerprint
+        ))),
+        CheckResult::NotFound => {
+            let pending = PendingKey {
+                host: host.to_string(),
+                port,
+                key_type: format!("{:?}", key_type),
+                fingerprint: fingerprint.clone(),
+                key: key.to_vec(),
+                format: key_type.into(),
+            };
+            PENDING_KEYS
+                .lock()
+                .unwrap()
+                .insert(host_entry(host, port), pending);
+            Err(CusError::App(format!(
+                "SSH host {} is unknown, fingerprint is {}. Trust it to continue",
+                host_entry(host, port),
+                fingerprint
+            )))
         }
-        let target_socket: HostSocketParams<'_> = HostSocketParams {
-            address: target_host,
-            port: config.target_port,
-        };
-        let ssh_params =
-            SshTunnelParams::new(jump_host, auth, target_socket).with_jump_host_port(config.port);
-        match SshJumper::open_tunnel(&ssh_params).await {
-            Ok((addr, rx)) => t.store_addr(addr, rx),
-            Err(e) => return Err(CusError::App(e.to_string())),
+        CheckResult::Failure => Err(CusError::App(format!(
+            "Can not check the SSH host key of {}",
+            host_entry(host, port)
+        ))),
+    }
+}
+
+// the host keys wait for trust
+pub fn get_pending_keys() -> Vec<PendingKey> {
+    PENDING_KEYS.lock().unwrap().values().cloned().collect()
+}
+
+// save the pending host key to the known_hosts of the app
+// the fingerprint must be the same as the one shown to the user
+pub fn trust_host_key(host: &str, port: u16, fingerprint: &str) -> Result<(), CusError> {
+    let entry = host_entry(host, port);
+    // a stale fingerprint keeps the pending key, so it can be confirmed again
+    let pending = {
+        let mut keys = PENDING_KEYS.lock().unwrap();
+        match keys.get(&entry) {
+            Some(p) if p.fingerprint == fingerprint => keys.remove(&entry).unwrap(),
+            _ => return Err(CusError::App(format!("No pending host key of {}", entry))),
         }
+    };
+    let session = async_ssh2_lite::ssh2::Session::new()?;
+    let mut known_hosts = session.known_hosts()?;
+    let path = get_known_hosts_path();
+    if path.exists() {
+        known_hosts.read_file(&path, KnownHostFileKind::OpenSSH)?;
     }
-    Ok(None)
+    known_hosts.add(&entry, &pending.key, "tauri-redis", pending.format)?;
+    known_hosts.write_file(&path, KnownHostFileKind::OpenSSH)?;
+    Ok(())
 }
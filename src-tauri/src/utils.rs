@@ -1,10 +1,6 @@
-use crate::err::CusError;
 use rand::distributions::Alphanumeric;
 use rand::prelude::*;
 
-use std::net::Ipv6Addr;
-use std::net::{IpAddr, Ipv4Addr};
-
 pub fn compare_version(version1: &str, version2: &str) -> i8 {
     let arr1: Vec<_> = version1.split(".").into_iter().collect();
     let arr2: Vec<_> = version2.split(".").into_iter().collect();
@@ -78,32 +74,158 @@ pub fn redis_str_to_binary(s: String) -> Vec<u8> {
     r
 }
 
-pub fn string_to_ip(s: &String) -> Result<IpAddr, CusError> {
-    let err = Err(CusError::App(String::from("not ip")));
-    let vec: Vec<_> = s.as_str().split(".").collect();
-    if vec.len() == 4 {
-        let mut ip: [u8; 4] = [0; 4];
-        for i in 0..vec.len() {
-            if let Ok(u) = vec[i].parse::<u8>() {
-                ip[i] = u;
+// split the line like redis-cli (sdssplitargs), none if the quotes are unbalanced
+pub fn split_args(line: &str) -> Option<Vec<Vec<u8>>> {
+    let bytes = line.as_bytes();
+    let mut args = vec![];
+    let mut i = 0;
+    loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            return Some(args);
+        }
+        let mut current = vec![];
+        let mut in_double = false;
+        let mut in_single = false;
+        loop {
+            if in_double {
+                let c = *bytes.get(i)?;
+                if c == b'\\'
+                    && i + 3 < bytes.len()
+                    && bytes[i + 1] == b'x'
+                    && bytes[i + 2].is_ascii_hexdigit()
+                    && bytes[i + 3].is_ascii_hexdigit()
+                {
+                    let hex = std::str::from_utf8(&bytes[i + 2..i + 4]).ok()?;
+                    current.push(u8::from_str_radix(hex, 16).ok()?);
+                    i += 3;
+                } else if c == b'\\' && i + 1 < bytes.len() {
+                    i += 1;
+                    current.push(match bytes[i] {
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'b' => 0x08,
+                        b'a' => 0x07,
+                        c => c,
+                    });
+                } else if c == b'"' {
+                    // the closing quote must be followed by a space or nothing
+                    if i + 1 < bytes.len() && !bytes[i + 1].is_ascii_whitespace() {
+                        return None;
+                    }
+                    i += 1;
+                    break;
+                } else {
+                    current.push(c);
+                }
+            } else if in_single {
+                let c = *bytes.get(i)?;
+                if c == b'\\' && bytes.get(i + 1) == Some(&b'\'') {
+                    i += 1;
+                    current.push(b'\'');
+                } else if c == b'\'' {
+                    if i + 1 < bytes.len() && !bytes[i + 1].is_ascii_whitespace() {
+                        return None;
+                    }
+                    i += 1;
+                    break;
+                } else {
+                    current.push(c);
+                }
             } else {
-                return err;
+                match bytes.get(i) {
+                    None => break,
+                    Some(c) if c.is_ascii_whitespace() => break,
+                    Some(b'"') => in_double = true,
+                    Some(b'\'') => in_single = true,
+                    Some(c) => current.push(*c),
+                }
             }
+            i += 1;
         }
-        return Ok(IpAddr::V4(Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3])));
+        args.push(current);
     }
-    if vec.len() == 8 {
-        let mut ip: [u16; 8] = [0; 8];
-        for i in 0..vec.len() {
-            if let Ok(u) = vec[i].parse::<u16>() {
-                ip[i] = u;
-            } else {
-                return err;
-            }
+}
+
+// quote the argument if needed, so split_args keeps it as one argument
+pub fn quote_arg(value: &str) -> String {
+    let plain = !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\'' || c == '\\');
+    if plain {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(line: &str) -> Option<Vec<String>> {
+        split_args(line).map(|v| {
+            v.into_iter()
+                .map(|a| String::from_utf8_lossy(&a).to_string())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn split_plain() {
+        assert_eq!(split("  set  foo bar ").unwrap(), vec!["set", "foo", "bar"]);
+        assert_eq!(split("").unwrap(), Vec::<String>::new());
+        assert_eq!(split("get a\tb").unwrap(), vec!["get", "a", "b"]);
+    }
+
+    #[test]
+    fn split_quoted() {
+        assert_eq!(
+            split(r#"set "a b" 'c d'"#).unwrap(),
+            vec!["set", "a b", "c d"]
+        );
+        assert_eq!(
+            split(r#"set "say \"hi\"" ''"#).unwrap(),
+            vec!["set", r#"say "hi""#, ""]
+        );
+        assert_eq!(split(r"set 'it\'s'").unwrap(), vec!["set", "it's"]);
+        // the single quote keeps the other escapes
+        assert_eq!(split(r"set 'a\nb'").unwrap(), vec!["set", r"a\nb"]);
+        assert_eq!(split(r#"set "a\nb\t""#).unwrap(), vec!["set", "a\nb\t"]);
+    }
+
+    #[test]
+    fn split_hex() {
+        let args = split_args(r#"set k "\x00\xfF\x41""#).unwrap();
+        assert_eq!(args[2], vec![0x00, 0xff, 0x41]);
+        // not two hex digits, the x is kept like redis-cli
+        let args = split_args(r#"set k "\x4g" "\x""#).unwrap();
+        assert_eq!(args[2], b"x4g".to_vec());
+        assert_eq!(args[3], b"x".to_vec());
+    }
+
+    #[test]
+    fn split_unbalanced() {
+        for line in [
+            r#"set "foo"#,
+            "set 'foo",
+            r#"set "foo"bar"#,
+            "set 'foo'bar",
+            r#"set "foo\""#,
+            r#"set "\"#,
+        ] {
+            assert_eq!(split(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn quote_round_trip() {
+        for arg in ["plain", "a b", r#"say "hi""#, r"back\slash", "it's", ""] {
+            let line = format!("set {}", quote_arg(arg));
+            assert_eq!(split(&line).unwrap()[1], arg);
         }
-        return Ok(IpAddr::V6(Ipv6Addr::new(
-            ip[0], ip[1], ip[2], ip[3], ip[4], ip[5], ip[6], ip[7],
-        )));
     }
-    err
 }
@@ -54,6 +54,10 @@ const Connection: React.FC<{
       if (connection.open !== true) {
         try {
           await store.connection.open(connection.id)
+          // select the default db of the connection
+          if (connection.is_cluster !== true) {
+            store.keyInfo.set(connection, connection.db ?? 0)
+          }
           setTimeout(() => {
             store.page.addPage({
               type: 'info',
@@ -65,7 +69,7 @@ const Connection: React.FC<{
         } catch {}
       }
     }
-  }, [connection, store.connection, store.page])
+  }, [connection, store.connection, store.keyInfo, store.page])
 
   const onItemClickTh = useThrottleFn(openConnection, {
     wait: 300
@@ -2,9 +2,10 @@ import type React from 'react'
 import datetime from './datetime'
 import text from './text'
 import json from './json'
+import phpUnserialize from './phpunserialize'
 
 export interface TypeFormat {
-  key: 'datetime' | 'json' | 'text'
+  key: 'datetime' | 'json' | 'text' | 'php_unserialize'
   label: string
   render: (content: string) => Promise<React.ReactNode>
 }
@@ -13,5 +14,6 @@ const items: Record<string, TypeFormat> = {}
 items[datetime.key] = datetime
 items[text.key] = text
 items[json.key] = json
+items[phpUnserialize.key] = phpUnserialize
 
 export default items
@@ -1,26 +1,41 @@
-// import { type TypeFormat } from '.'
-// import React from 'react'
-// import Error from '../Err'
-// import request from '@/utils/request'
-//
-// const item: TypeFormat = {
-//   key: 'php_unserialize',
-//   label: 'Php Unserialize',
-//   render: async (content) => {
-//     try {
-//       const res = await request<string>(
-//         'transfer/php_unserialize',
-//         0,
-//         {
-//           data: content
-//         },
-//         { showNotice: false }
-//       )
-//       return res.data
-//     } catch (err) {
-//       return <Error message={err as string} />
-//     }
-//   }
-// }
-//
-// export default item
+import { type TypeFormat } from '.'
+import React from 'react'
+import lodash from 'lodash'
+import Error from '../Err'
+import JsonView from '@/components/JsonView'
+import request from '@/utils/request'
+
+const item: TypeFormat = {
+  key: 'php_unserialize',
+  label: 'Php Unserialize',
+  render: async (content) => {
+    try {
+      const res = await request<any>(
+        'transfer/php_unserialize',
+        0,
+        {
+          data: content
+        },
+        { showNotice: false }
+      )
+      if (lodash.isObject(res.data)) {
+        return (
+          <JsonView
+            validationMessage="error"
+            displayDataTypes={false}
+            style={{
+              wordBreak: 'break-all',
+              color: '#FFFFFF'
+            }}
+            src={res.data}
+          ></JsonView>
+        )
+      }
+      return JSON.stringify(res.data)
+    } catch (err) {
+      return <Error message={err as string} />
+    }
+  }
+}
+
+export default item
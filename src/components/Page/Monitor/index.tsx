@@ -24,17 +24,27 @@ const Monitor: React.FC<{
     },
     (r) => {
       try {
-        const message: APP.EventPayload<string> = JSON.parse(r.payload)
+        const message: APP.EventPayload<APP.MonitorEvent> = JSON.parse(
+          r.payload
+        )
+        if (message.data.kind === 'stats') {
+          const top = message.data.top_commands
+            .map((v) => `${v.name}:${v.count}`)
+            .join(' ')
+          term.current?.writeln(`[${message.data.total}] ${top}`)
+          return
+        }
+        const line = message.data.raw
         if (search.current !== '') {
           if (
-            message.data
+            line
               .toLocaleLowerCase()
               .includes(search.current.toLocaleLowerCase())
           ) {
-            term.current?.writeln(message.data)
+            term.current?.writeln(line)
           }
         } else {
-          term.current?.writeln(message.data)
+          term.current?.writeln(line)
         }
       } catch (e) {
         console.log(e)
@@ -8,6 +8,7 @@ import { useEventListen } from '@/hooks/useEventListen'
 import CusButton from '@/components/CusButton'
 import { getCurrentWindow } from '@tauri-apps/api/window'
 import { useTranslation } from 'react-i18next'
+import { confirm } from '@tauri-apps/plugin-dialog'
 
 interface EventName {
   send: string
@@ -21,6 +22,9 @@ const Terminal: React.FC<{
 
   const [event, setEvent] = React.useState<EventName>()
 
+  // the listener is registered once, the send event is read from the ref
+  const sendEvent = React.useRef<string>()
+
   const {t} = useTranslation()
 
   const clear = React.useCallback(async () => {
@@ -36,6 +40,7 @@ const Terminal: React.FC<{
       return await request<EventName>('terminal/open', connection.id).then(
         (res) => {
           setEvent(res.data)
+          sendEvent.current = res.data.send
           setTimeout(() => {
             term.current?.writeln('connected...')
           }, 200)
@@ -45,6 +50,24 @@ const Terminal: React.FC<{
     },
     (v) => {
       const data = v.payload.data
+      // the write to the production connection is run after confirmed
+      if (!v.payload.success && data === 'CONFIRM_REQUIRED') {
+        void confirm(
+          t('This is a production connection, are you sure to write?')
+        ).then((ok) => {
+          if (sendEvent.current !== undefined) {
+            const payload: APP.EventPayload<{ confirm: boolean }> = {
+              data: { confirm: ok },
+              id: 1,
+              time: '',
+              success: true,
+              event: sendEvent.current
+            }
+            emit(sendEvent.current, payload)
+          }
+        })
+        return
+      }
       if (v.payload.success) {
         term.current?.writeRedisResult(data)
       } else {
@@ -64,20 +87,85 @@ const Terminal: React.FC<{
 
   const [cmd, setCmd] = React.useState('')
 
+  const [completion, setCompletion] = React.useState<APP.Completion>()
+
+  const complete = React.useCallback(
+    async (line: string) => {
+      const res = await request<APP.Completion>(
+        'terminal/complete',
+        connection.id,
+        {
+          line
+        }
+      ).catch(() => undefined)
+      setCompletion(res?.data)
+    },
+    [connection.id]
+  )
+
+  const options = React.useMemo(() => {
+    // the last typed word is replaced by the completion
+    const base = /\s$/.test(cmd) ? cmd : cmd.replace(/\S*$/, '')
+    return (completion?.items ?? []).map((v) => {
+      return {
+        label: v.label,
+        value: `${base}${v.label} `
+      }
+    })
+  }, [cmd, completion])
+
+  // stop the streaming or blocking command of the session
+  const interrupt = React.useCallback(() => {
+    if (event !== undefined) {
+      const payload: APP.EventPayload<{ interrupt: boolean }> = {
+        data: { interrupt: true },
+        id: 1,
+        time: '',
+        success: true,
+        event: event.send
+      }
+      emit(event.send, payload)
+    }
+  }, [event])
+
   const history = React.useRef<string[]>([])
   const historyIndex = React.useRef(0)
 
+  // the history is kept in sqlite per connection
+  React.useEffect(() => {
+    request<APP.TerminalHistory[]>('terminal/history', connection.id, {
+      limit: 100
+    })
+      .then((res) => {
+        history.current = res.data.map((v) => v.command).reverse()
+        historyIndex.current = history.current.length
+      })
+      .catch(() => {})
+  }, [connection.id])
+
   return (
     <Page pageKey={pageKey}>
       <XTerm className="rounded-b-none" ref={term} />
       <AutoComplete
         value={cmd}
         className="w-full"
-        options={[]}
+        options={options}
+        defaultActiveFirstOption={false}
         onChange={(e) => {
           setCmd(e)
+          void complete(e)
         }}
         onKeyDown={(e) => {
+          const input = e.target as HTMLInputElement
+          if (
+            e.ctrlKey &&
+            e.key === 'c' &&
+            input.selectionStart === input.selectionEnd
+          ) {
+            e.preventDefault()
+            interrupt()
+            return
+          }
           switch (e.key) {
             case 'ArrowUp': {
               e.preventDefault()
@@ -103,18 +191,15 @@ const Terminal: React.FC<{
               break
             }
             case 'Enter': {
-              const parseCmd = cmd
-                .split(' ')
-                .map((v) => v.trim())
-                .filter((v) => v !== '')
-              if (parseCmd.length > 0) {
-                const cmdStr = parseCmd.join(' ')
+              // the line is split in rust like redis-cli, quotes and \x escapes are kept
+              const cmdStr = cmd.trim()
+              if (cmdStr !== '') {
                 term.current?.writeln(`> ${cmdStr}`)
                 history.current.push(cmdStr)
                 historyIndex.current = history.current.length
                 if (event !== undefined) {
-                  const payload: APP.EventPayload<string[]> = {
-                    data: parseCmd,
+                  const payload: APP.EventPayload<string> = {
+                    data: cmdStr,
                     id: 1,
                     time: '13232',
                     success: true,
@@ -136,6 +221,12 @@ const Terminal: React.FC<{
           }}
         />
       </AutoComplete>
+      {completion?.signature !== undefined && completion.signature !== null && (
+        <div className="pt-1 text-gray-500">
+          {completion.signature}
+          {completion.summary !== null && ` - ${completion.summary}`}
+        </div>
+      )}
       <div className="pt-2">
         <Space>
           <span>
@@ -147,6 +238,9 @@ const Terminal: React.FC<{
               {t("Clear")}
             </CusButton>
           </span>
+          <span>
+            <CusButton onClick={interrupt}>{t('Interrupt')}</CusButton>
+          </span>
           <span>
               {t("Enter to execute command, Arrow Down/Arrow Up to switch history")}
           </span>
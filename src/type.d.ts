@@ -1,4 +1,13 @@
 declare namespace APP {
+  interface SshHost {
+    host: string
+    port: number
+    username: string
+    password: string | null
+    private_key: string | null
+    passphrase: string | null
+    agent: boolean
+  }
   interface Connection {
     id: number
     host: string
@@ -15,6 +24,30 @@ declare namespace APP {
     ssh_private_key: string | null
     ssh_timeout: number | null
     ssh_username: string | null
+    read_from_replica: boolean
+    replica_host: string | null
+    replica_port: number | null
+    connect_timeout: number | null
+    response_timeout: number | null
+    ssh_agent: boolean
+    ssh_jumps: SshHost[]
+    proxy_type: 'socks5' | 'http' | null
+    proxy_host: string | null
+    proxy_port: number | null
+    proxy_username: string | null
+    proxy_password: string | null
+    socket_path: string | null
+    protocol: 2 | 3 | null
+    tls: boolean
+    db: number | null
+    sentinel_master: string | null
+    sentinel_username: string | null
+    sentinel_password: string | null
+    group: string | null
+    tags: string[]
+    color: string | null
+    environment: string | null
+    sort: number
     nodes?: Node[]
     dbs?: Database[]
     open?: boolean
@@ -35,17 +68,43 @@ declare namespace APP {
     count: number
   }
 
+  interface SlotRange {
+    start: number
+    end: number
+  }
+
+  interface SlotTransfer {
+    slot: number
+    node: string
+  }
+
   interface Node {
-    config_epoch: string
+    config_epoch: number
     port: number
+    cport: number | null
+    hostname: string | null
+    shard_id: string | null
     flags: string
+    flag_set: {
+      myself: boolean
+      master: boolean
+      replica: boolean
+      pfail: boolean
+      fail: boolean
+      handshake: boolean
+      noaddr: boolean
+      nofailover: boolean
+    }
     host: string
     id: string
     link_state: string
-    master: string
+    master: string | null
     ping_sent: number
     pong_recv: number
     slot: string
+    slots: SlotRange[]
+    migrating: SlotTransfer[]
+    importing: SlotTransfer[]
   }
 
   interface SlowLog {
@@ -136,17 +195,135 @@ declare namespace APP {
   interface PubsubMessage {
     payload: string
     channel: string
+    pattern: string | null
+    sharded: boolean
+  }
+
+  interface ChannelStat {
+    channel: string
+    subscribers: number
+    sharded: boolean
+  }
+
+  interface PubsubStat {
+    channels: ChannelStat[]
+    patterns: number
+  }
+
+  interface KeyEvent {
+    key: string
+    event: string
+    db: number
+    host: string
+  }
+
+  interface ScriptProgress {
+    sent: number
+    succeeded: number
+    failed: number
+    read_bytes: number
+    total_bytes: number
+    errors: Array<{ line: number; command: string; error: string }>
+    done: boolean
+    stopped: boolean
+  }
+
+  interface TerminalHistory {
+    id: number
+    connection_id: number
+    command: string
+    db: number
+    success: boolean
+    duration: number
+    created_at: string
+  }
+
+  interface Snippet {
+    id?: number
+    connection_id?: number
+    name: string
+    template: string
+    created_at?: string
+    params?: string[]
+  }
+
+  interface Completion {
+    items: Array<{
+      label: string
+      kind: 'command' | 'subcommand' | 'token' | 'key'
+    }>
+    signature: string | null
+    summary: string | null
+  }
+
+  interface MonitorLine {
+    kind: 'line'
+    timestamp: number
+    db: number
+    client: string
+    client_type: 'tcp' | 'unix' | 'lua'
+    command: string
+    args: string[]
+    raw: string
+  }
+
+  interface MonitorStats {
+    kind: 'stats'
+    seconds: number
+    total: number
+    top_commands: Array<{ name: string; count: number }>
+    top_keys: Array<{ name: string; count: number }>
+  }
+
+  // the line not parsed, only emitted without filters
+  interface MonitorRaw {
+    kind: 'raw'
+    raw: string
+  }
+
+  type MonitorEvent = MonitorLine | MonitorRaw | MonitorStats
+
+  interface Recording {
+    id: number
+    connection_id: number
+    name: string
+    types: 'pubsub' | 'monitor'
+    count: number
+    size: number
+    created_at: string
+    ended_at: string | null
+  }
+
+  interface RecordedMessage {
+    elapsed: number
+    channel: string | null
+    pattern: string | null
+    sharded: boolean
+    payload: string
   }
 
   interface RedisCmd {
     id: string
+    connection_id: number | null
     cmd: string
     response: any
     host: string
+    success: boolean
     created_at: string
     duration: number
   }
 
+  interface CommandLog {
+    id: number
+    connection_id: number | null
+    host: string
+    cmd: string
+    response: any
+    success: boolean
+    duration: number
+    created_at: string
+  }
+
   interface SingleScanLikeResp<T = string> {
     cursor: string
     values: T[]
@@ -2,6 +2,11 @@ import { invoke } from '@tauri-apps/api/core'
 import { isObject, isString } from 'lodash'
 import  {emit} from '@tauri-apps/api/event'
 import {ERROR_NOTIFICATION} from '@/consts/event'
+import { confirm } from '@tauri-apps/plugin-dialog'
+import i18n from '@/i18n'
+
+// the backend rejects writes to production connection until confirmed
+const CONFIRM_REQUIRED = 'CONFIRM_REQUIRED'
 
 export interface Response<T> {
   data: T
@@ -30,7 +35,21 @@ export default async function request<T = any>(
       cid,
       payload: JSON.stringify(args)
     }
-    const res = await invoke('dispatch', params)
+    let res: unknown
+    try {
+      res = await invoke('dispatch', params)
+    } catch (err) {
+      if (err !== CONFIRM_REQUIRED) {
+        throw err
+      }
+      const confirmed = await confirm(
+        i18n.t('This is a production connection, are you sure to write?')
+      )
+      if (!confirmed) {
+        throw i18n.t('Canceled')
+      }
+      res = await invoke('dispatch', { ...params, confirmed: true })
+    }
     const data = JSON.parse(res as string)
     return data as Response<T>
   } catch (err) {
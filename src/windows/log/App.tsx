@@ -22,15 +22,24 @@ const App: React.FC = () => {
 
   const { t } = useTranslation()
 
+  // the commands are saved to the history only if enabled
+  const [persist, setPersist] = React.useState(false)
+
+  React.useEffect(() => {
+    request<{ enabled: boolean }>('debug/persist')
+      .then((res) => {
+        setPersist(res.data.enabled)
+      })
+      .catch(() => {})
+  }, [])
+
   useEventListen<string>(
     async () => {
-      return await request('debug/log').then(() => {
-        return 'debug'
-      })
+      const res = await request<string>('debug/log')
+      return res.data
     },
     (e) => {
       const cmd: APP.RedisCmd = JSON.parse(e.payload)
-      console.log(e.payload)
       if (
         searchRef.current === '' ||
         cmd.cmd
@@ -45,8 +54,8 @@ const App: React.FC = () => {
       }
     },
     getCurrentWindow(),
-    async () => {
-      return await request('debug/cancel')
+    async (name) => {
+      return await request('debug/cancel', 0, { name })
     }
   )
 
@@ -82,6 +91,22 @@ const App: React.FC = () => {
                   ></Checkbox>
                 </Space>
               </div>
+              <div>
+                <Space>
+                  <span>{t('Save History')}</span>
+                  <Checkbox
+                    checked={persist}
+                    onChange={(e) => {
+                      const enabled = e.target.checked
+                      request('debug/persist/set', 0, { enabled })
+                        .then(() => {
+                          setPersist(enabled)
+                        })
+                        .catch(() => {})
+                    }}
+                  ></Checkbox>
+                </Space>
+              </div>
             </Space>
           </div>
         </Page>